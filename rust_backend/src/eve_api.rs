@@ -1,22 +1,27 @@
 use dotenv::dotenv;
+use std::collections::HashMap;
 use std::env;
 use std::error::Error;
 use std::fs::{self, File};
 use std::io::{self, Read, Write};
 use std::path::Path;
 use std::time::{SystemTime, UNIX_EPOCH, Duration};
-use std::sync::Arc;
 
 use chrono::{DateTime, Utc};
+use futures::stream::{self, StreamExt};
 use serde::{Deserialize, Serialize};
-use tokio::{task, time};
-use tokio::sync::Semaphore;
 
 
 use crate::pathfinding::Location;
-use crate::market::Order;
+use crate::market::{Candle, Order};
+use crate::retry::RetryPolicy;
 
-const TOKEN_FILE: &str = "token.json"; 
+const TOKEN_FILE: &str = "token.json";
+
+// Backs off `base * 2^attempt` plus jitter, up to this many retries (after
+// the first attempt), before giving up on a single ESI call.
+const MAX_RETRIES: u32 = 5;
+const BASE_BACKOFF: Duration = Duration::from_millis(250);
 
 use std::sync::Mutex;
 
@@ -35,6 +40,38 @@ struct TokenResponse {
     expires_in: u64, 
 }
 
+/// Shared by every ESI GET handler that needs to know when its response
+/// goes stale (`request_order_metadata`, `request_market_history`).
+fn parse_expires_header(response: &reqwest::Response) -> Result<DateTime<Utc>, Box<dyn Error>> {
+    match response.headers().get("expires") {
+        Some(expiry_value) => {
+            match expiry_value.to_str() {
+                Ok(value_str) => {
+                    match DateTime::parse_from_rfc2822(value_str) {
+                        Ok(expiry_date) => Ok(expiry_date.with_timezone(&Utc)),
+                        Err(_) => Err("Failed to parse expiry date".into()),
+                    }
+                }
+                Err(_) => Err("Invalid expiry header value".into()),
+            }
+        }
+        None => Err("Expires header not found".into()),
+    }
+}
+
+pub enum OrderMetadata {
+    NotModified { expiry: DateTime<Utc> },
+    Modified { x_pages: u64, expiry: DateTime<Utc>, etag: Option<String> },
+}
+
+/// Either the region's order book came back `304 Not Modified` (caller
+/// should keep its cached `Vec<Order>` and just push `expiry` forward), or
+/// every page was re-fetched fresh.
+pub enum OrdersFetch {
+    NotModified { expiry: DateTime<Utc> },
+    Modified { orders: Vec<Order>, expiry: DateTime<Utc>, etag: Option<String> },
+}
+
 pub struct EveApiClient {
     client_id: String,
     client_secret: String,
@@ -42,6 +79,7 @@ pub struct EveApiClient {
     redirect_url: String,
     access_token: Mutex<Option<String>>,
     expires_at: Mutex<Option<u64>>,
+    retry_policy: RetryPolicy,
 }
 
 
@@ -66,6 +104,7 @@ impl EveApiClient {
             redirect_url,
             access_token: Mutex::new(token_data.clone().map(|data| data.access_token)),
             expires_at: Mutex::new(token_data.map(|data| data.expires_at)),
+            retry_policy: RetryPolicy::new(MAX_RETRIES, BASE_BACKOFF)?,
         })
     }
 
@@ -169,20 +208,43 @@ impl EveApiClient {
     }
 
 
+    /// Pushes a single waypoint into the in-game autopilot, using the
+    /// `esi-ui.write_waypoint.v1` scope already requested in
+    /// `get_authorization_url`.
+    pub async fn set_waypoint(
+        &self,
+        destination_id: u64,
+        clear_other_waypoints: bool,
+        add_to_beginning: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        self.ensure_valid_token().await?;
+
+        let url = format!(
+            "https://esi.evetech.net/latest/ui/autopilot/waypoint/?datasource=tranquility&destination_id={}&clear_other_waypoints={}&add_to_beginning={}",
+            destination_id, clear_other_waypoints, add_to_beginning
+        );
+
+        let access_token = self.get_access_token().unwrap();
+        self.retry_policy
+            .post(&url, |request| request.bearer_auth(&access_token))
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
     pub async fn request_location(&self) -> Result<Location, Box<dyn Error>> {
         self.ensure_valid_token().await?;
 
         let url = format!("https://esi.evetech.net/latest/characters/{}/location/", self.character_id);
-    
+
         println!("{:?}", url);
 
-        let client = reqwest::Client::new();
-    
-        let response = client.get(url)
-            .bearer_auth(self.get_access_token().unwrap())
-            .send()
+        let access_token = self.get_access_token().unwrap();
+        let response = self.retry_policy
+            .get(&url, |request| request.bearer_auth(&access_token))
             .await?;
-    
+
         println!("{:?}", response);
 
         if response.status().is_success() {
@@ -193,15 +255,27 @@ impl EveApiClient {
         }
     }
 
-    pub async fn request_order_metadata(&self, region: u64) -> Result<(u64, DateTime<Utc>), Box<dyn Error>> {
+    /// Either the first page came back unchanged (`304`, `expiry` pushed
+    /// forward by ESI's fresh `expires` header), or it's new data along with
+    /// the page count to fetch the rest and the `ETag` to cache for next
+    /// time.
+    pub async fn request_order_metadata(&self, region: u64, etag: Option<&str>) -> Result<OrderMetadata, Box<dyn Error>> {
         let url = format!("https://esi.evetech.net/latest/markets/{}/orders/?datasource=tranquility&order_type=all&page=1", region);
-    
-        let client = reqwest::Client::new();
-        let response = client.get(&url)
-            .send()
+
+        let response = self.retry_policy
+            .get(&url, |request| match etag {
+                Some(etag) => request.header("If-None-Match", etag),
+                None => request,
+            })
             .await
             .map_err(|e| e.to_string())?;
-    
+
+        let expiry = parse_expires_header(&response)?;
+
+        if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+            return Ok(OrderMetadata::NotModified { expiry });
+        }
+
         let x_pages = match response.headers().get("x-pages") {
             Some(x_pages_value) => {
                 match x_pages_value.to_str() {
@@ -216,65 +290,91 @@ impl EveApiClient {
             }
             None => return Err("X-Pages header not found".into()),
         };
-    
-        let expiry_unix_time = match response.headers().get("expires") {
-            Some(expiry_value) => {
-                match expiry_value.to_str() {
-                    Ok(value_str) => {
-                        match DateTime::parse_from_rfc2822(value_str) {
-                            Ok(expiry_date) => expiry_date.with_timezone(&Utc),
-                            Err(_) => return Err("Failed to parse expiry date".into()),
-                        }
-                    }
-                    Err(_) => return Err("Invalid expiry header value".into()),
-                }
-            }
-            None => return Err("Expires header not found".into()),
-        };
-    
-        Ok((x_pages, expiry_unix_time))
-    }
 
+        let etag = response.headers().get("etag")
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_string());
 
-    
-    pub async fn request_orders(&self, region: u64) -> Result<(Vec<Order>, DateTime<Utc>), Box<dyn Error>> {
-        let (x_pages, expiry) = self.request_order_metadata(region).await?;
+        Ok(OrderMetadata::Modified { x_pages, expiry, etag })
+    }
 
-        let client = reqwest::Client::builder()
-            .timeout(Duration::from_secs(30))
-            .build()?;
+    /// Fetches one `type_id`'s daily candle series from
+    /// `/markets/{region_id}/history/`.
+    pub async fn request_market_history(&self, region: u64, type_id: u64) -> Result<(Vec<Candle>, DateTime<Utc>), Box<dyn Error>> {
+        let url = format!("https://esi.evetech.net/latest/markets/{}/history/?datasource=tranquility&type_id={}", region, type_id);
 
-        let semaphore = Arc::new(Semaphore::new(10));
-        let mut tasks = Vec::new();
+        let response = self.retry_policy.get(&url, |request| request).await?;
+        let expiry = parse_expires_header(&response)?;
+        let candles = response.error_for_status()?.json::<Vec<Candle>>().await?;
 
-        for page in 1..=x_pages {
-            let permit = semaphore.clone().acquire_owned().await?;
-            let client = client.clone();
-            let url = format!("https://esi.evetech.net/latest/markets/{}/orders/?datasource=tranquility&order_type=all&page={}", region, page);
+        Ok((candles, expiry))
+    }
 
-            let task = task::spawn(async move {
-                time::sleep(Duration::from_millis(20)).await;
-                let response = client.get(&url).send().await?;
-                println!("{:?}", &url);
-                drop(permit); 
-                response.error_for_status()?.json::<Vec<Order>>().await
-            });
+    /// Fetches history for every `type_id` currently seen in the order
+    /// book, concurrently bounded the same way `request_orders` bounds its
+    /// page fetches — ESI has no single "all types" history endpoint.
+    pub async fn request_market_history_batch(
+        &self,
+        region: u64,
+        type_ids: &[u64],
+    ) -> Result<(HashMap<u64, Vec<Candle>>, DateTime<Utc>), Box<dyn Error>> {
+        let results: Vec<Result<(u64, Vec<Candle>, DateTime<Utc>), Box<dyn Error>>> = stream::iter(type_ids.to_vec())
+            .map(|type_id| async move {
+                let (candles, expiry) = self.request_market_history(region, type_id).await?;
+                Ok((type_id, candles, expiry))
+            })
+            .buffer_unordered(10)
+            .collect()
+            .await;
 
-            tasks.push(task);
+        let mut by_type = HashMap::new();
+        let mut latest_expiry: Option<DateTime<Utc>> = None;
+        for result in results {
+            let (type_id, candles, expiry) = result?;
+            by_type.insert(type_id, candles);
+            latest_expiry = Some(latest_expiry.map_or(expiry, |current: DateTime<Utc>| current.max(expiry)));
         }
 
+        // History updates once a day; with no type_ids to fetch (e.g. an
+        // empty order book) there's nothing to derive a real expiry from,
+        // so just don't retry again for a day.
+        let expiry = latest_expiry.unwrap_or_else(|| Utc::now() + chrono::Duration::hours(24));
+
+        Ok((by_type, expiry))
+    }
+
+
+    
+    /// `etag` is the region's cached `If-None-Match` value, if any; a `304`
+    /// on the first page short-circuits straight to `OrdersFetch::NotModified`
+    /// without touching the remaining pages.
+    pub async fn request_orders(&self, region: u64, etag: Option<&str>) -> Result<OrdersFetch, Box<dyn Error>> {
+        let (x_pages, expiry, new_etag) = match self.request_order_metadata(region, etag).await? {
+            OrderMetadata::NotModified { expiry } => return Ok(OrdersFetch::NotModified { expiry }),
+            OrderMetadata::Modified { x_pages, expiry, etag } => (x_pages, expiry, etag),
+        };
+
+        // The retry policy already throttles every request against ESI's
+        // shared error budget, so page fetches just need bounding for
+        // in-flight concurrency, not a blind per-request sleep.
+        let results: Vec<Result<Vec<Order>, Box<dyn Error>>> = stream::iter(1..=x_pages)
+            .map(|page| async move {
+                let url = format!("https://esi.evetech.net/latest/markets/{}/orders/?datasource=tranquility&order_type=all&page={}", region, page);
+                let response = self.retry_policy.get(&url, |request| request).await?;
+                println!("{:?}", &url);
+                let page_orders = response.error_for_status()?.json::<Vec<Order>>().await?;
+                Ok(page_orders)
+            })
+            .buffer_unordered(10)
+            .collect()
+            .await;
+
         let mut orders = Vec::new();
-        for task in tasks {
-            match task.await {
-                Ok(result) => match result {
-                    Ok(mut page_orders) => orders.append(&mut page_orders),
-                    Err(e) => return Err(Box::new(e)),
-                },
-                Err(e) => return Err(Box::new(e)),
-            }
+        for result in results {
+            orders.append(&mut result?);
         }
 
-        Ok((orders, expiry))
+        Ok(OrdersFetch::Modified { orders, expiry, etag: new_etag })
     }
 
     pub async fn ensure_valid_token(&self) -> Result<(), Box<dyn Error>> {
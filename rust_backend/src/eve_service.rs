@@ -1,9 +1,19 @@
-use crate::eve_api::EveApiClient;
+use crate::eve_api::{EveApiClient, OrdersFetch};
 use crate::pathfinding::Location;
-use crate::market::Orders;
+use crate::market::{MarketHistory, Order, Orders};
+use crate::route::RouteWaypoint;
 
+use std::collections::HashSet;
 use std::error::Error;
 
+use chrono::{DateTime, Utc};
+use futures::stream::{self, StreamExt};
+
+// Bounds how many regions' order books we fetch at once — mirrors the
+// concurrency cap `EveApiClient` already uses for per-region page/history
+// fan-out.
+const REGION_CONCURRENCY: usize = 10;
+
 pub struct EveService {
     client: EveApiClient,
 }
@@ -26,17 +36,73 @@ impl EveService {
         self.client.request_location().await.map_err(|e| e.into())
     }
 
+    /// Pushes a planned route into the in-game autopilot, clearing any
+    /// existing waypoints before queuing the new ones in order.
+    pub async fn set_waypoints(&self, waypoints: &[RouteWaypoint]) -> Result<(), Box<dyn Error>> {
+        for (index, waypoint) in waypoints.iter().enumerate() {
+            self.client
+                .set_waypoint(waypoint.system_id, index == 0, false)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Refetches every expired region concurrently instead of one at a
+    /// time — most regions haven't actually changed, so each request sends
+    /// the cached `ETag` as `If-None-Match` and a `304` just pushes that
+    /// region's expiry forward, reusing its cached orders, rather than
+    /// re-downloading and re-parsing pages that are identical to last time.
     pub async fn updated_orders(&self, current_orders: &Orders) -> Result<Orders, Box<dyn Error>> {
         let mut region_orders = Orders::new();
 
         let regions = current_orders.expired_regions();
 
-        for &region in regions.iter() {
-                let (orders, expiry) = self.client.request_orders(region).await?;
-                region_orders.insert(region, orders, expiry);
+        let results: Vec<Result<(u64, Vec<Order>, DateTime<Utc>, Option<String>), Box<dyn Error>>> = stream::iter(regions)
+            .map(|region| async move {
+                let etag = current_orders.etag(region);
+
+                match self.client.request_orders(region, etag).await? {
+                    OrdersFetch::NotModified { expiry } => {
+                        let orders = current_orders.region_orders(region);
+                        let etag = current_orders.etag(region).map(|etag| etag.to_string());
+                        Ok((region, orders, expiry, etag))
+                    }
+                    OrdersFetch::Modified { orders, expiry, etag } => Ok((region, orders, expiry, etag)),
+                }
+            })
+            .buffer_unordered(REGION_CONCURRENCY)
+            .collect()
+            .await;
+
+        for result in results {
+            let (region, orders, expiry, etag) = result?;
+            region_orders.insert(region, orders, expiry, etag);
         }
-        
+
         Ok(region_orders)
+    }
+
+    /// Refreshes any region whose market-history snapshot has expired,
+    /// fetching candles only for the `type_id`s currently seen in
+    /// `current_orders` — there's no bulk "every type" history endpoint,
+    /// and types with no live orders have nothing to trade anyway.
+    pub async fn updated_market_history(&self, current_history: &MarketHistory, current_orders: &Orders) -> Result<MarketHistory, Box<dyn Error>> {
+        let mut region_history = MarketHistory::new();
+
+        let regions = current_history.expired_regions();
+        let type_ids: Vec<u64> = current_orders.orders()
+            .iter()
+            .map(|order| order.type_id())
+            .collect::<HashSet<u64>>()
+            .into_iter()
+            .collect();
+
+        for &region in regions.iter() {
+            let (candles_by_type, expiry) = self.client.request_market_history_batch(region, &type_ids).await?;
+            region_history.insert(region, candles_by_type, expiry);
+        }
 
+        Ok(region_history)
     }
 }
@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+
+use serde::Deserialize;
+
+/// Static item-type metadata from the SDE, keyed by `type_id` — everything
+/// the rest of the crate needs to know about *what* an order or trade is
+/// moving, as opposed to `Order`/`Trade`, which know the market side.
+#[derive(Debug, Clone)]
+pub struct ItemType {
+    pub type_id: u64,
+    pub name: String,
+    pub group_id: u64,
+    /// Packaged volume in m³ — what actually occupies cargo hold space,
+    /// not the (often much larger) assembled volume.
+    pub volume: f64,
+}
+
+#[derive(Deserialize, Debug)]
+#[allow(non_snake_case)]
+struct RawInvType {
+    typeID: u64,
+    groupID: u64,
+    typeName: String,
+    volume: Option<f64>,
+}
+
+#[derive(Deserialize, Debug)]
+#[allow(non_snake_case)]
+struct RawInvVolume {
+    typeID: u64,
+    volume: f64,
+}
+
+/// Mirrors `pathfinding::parse_stations`: loads `invTypes.csv` for name,
+/// group, and assembled volume, then overlays `invVolumes.csv` where it has
+/// a packaged volume for the same `type_id` (ships and other items that get
+/// repackaged for cargo are the whole reason that file exists).
+pub fn parse_item_types() -> Result<HashMap<u64, ItemType>, Box<dyn Error>> {
+    let types_file = File::open("../invTypes.csv")?;
+    let mut types_rdr = csv::Reader::from_reader(types_file);
+
+    let mut items: HashMap<u64, ItemType> = HashMap::new();
+    for result in types_rdr.deserialize() {
+        match result {
+            Ok(raw) => {
+                let raw: RawInvType = raw;
+                items.insert(raw.typeID, ItemType {
+                    type_id: raw.typeID,
+                    name: raw.typeName,
+                    group_id: raw.groupID,
+                    volume: raw.volume.unwrap_or_default(),
+                });
+            }
+            Err(e) => println!("Error deserializing record: {}", e),
+        }
+    }
+
+    let volumes_file = File::open("../invVolumes.csv")?;
+    let mut volumes_rdr = csv::Reader::from_reader(volumes_file);
+
+    for result in volumes_rdr.deserialize() {
+        match result {
+            Ok(raw) => {
+                let raw: RawInvVolume = raw;
+                if let Some(item) = items.get_mut(&raw.typeID) {
+                    item.volume = raw.volume;
+                }
+            }
+            Err(e) => println!("Error deserializing record: {}", e),
+        }
+    }
+
+    println!("item types: {:?}", items.len());
+
+    Ok(items)
+}
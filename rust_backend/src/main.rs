@@ -1,16 +1,48 @@
 mod manager;
 mod eve_service;
 mod eve_api;
+mod items;
 mod pathfinding;
 mod market;
+mod retry;
+mod route;
+mod storage;
 
 use manager::Manager;
 
 use actix_web::{web, App, HttpServer, Responder, HttpResponse, http::header};
-use std::sync::Mutex;
+use actix_web::web::Bytes;
+use futures::stream::{self, Stream, StreamExt};
 use tokio::sync::watch;
+use tokio_stream::wrappers::WatchStream;
 use serde::Deserialize;
 
+enum Event {
+    Location(Option<pathfinding::Location>),
+    Orders(Vec<market::Order>),
+}
+
+impl Event {
+    fn into_sse_frame(self) -> Bytes {
+        let (name, payload) = match self {
+            Event::Location(location) => ("location", serde_json::to_string(&location)),
+            Event::Orders(orders) => ("orders", serde_json::to_string(&orders)),
+        };
+
+        let payload = payload.unwrap_or_else(|_| "null".to_string());
+        Bytes::from(format!("event: {}\ndata: {}\n\n", name, payload))
+    }
+}
+
+fn event_stream(manager: &Manager) -> impl Stream<Item = Result<Bytes, actix_web::Error>> {
+    let location_events = WatchStream::new(manager.subscribe_location())
+        .map(Event::Location);
+    let order_events = WatchStream::new(manager.subscribe_orders())
+        .map(|orders| Event::Orders(orders.orders()));
+
+    stream::select(location_events, order_events).map(|event| Ok(event.into_sse_frame()))
+}
+
 
 async fn index() -> impl Responder {
     "Hi"
@@ -30,9 +62,78 @@ async fn location(manager: web::Data<Manager>) -> impl Responder {
     }
 }
 
-async fn orders(manager: web::Data<Manager>) -> impl Responder {
-    let orders = manager.orders();
-    HttpResponse::Ok().json(orders)
+const DEFAULT_ORDERS_PAGE_LIMIT: usize = 500;
+
+#[derive(Deserialize)]
+struct OrdersQuery {
+    region: Option<u64>,
+    type_id: Option<u64>,
+    min_profit: Option<f64>,
+    limit: Option<usize>,
+    cursor: Option<String>,
+}
+
+async fn orders(query: web::Query<OrdersQuery>, manager: web::Data<Manager>) -> impl Responder {
+    let filter = market::OrderFilter {
+        region: query.region,
+        type_id: query.type_id,
+        min_profit: query.min_profit,
+    };
+    let limit = query.limit.unwrap_or(DEFAULT_ORDERS_PAGE_LIMIT);
+
+    let page = manager.orders_page(&filter, limit, query.cursor.as_deref());
+    HttpResponse::Ok().json(page)
+}
+
+#[derive(Deserialize)]
+struct SearchQuery {
+    q: String,
+}
+
+async fn search(query: web::Query<SearchQuery>, manager: web::Data<Manager>) -> impl Responder {
+    HttpResponse::Ok().json(manager.search(&query.q))
+}
+
+async fn history(manager: web::Data<Manager>) -> impl Responder {
+    HttpResponse::Ok().json(manager.market_history())
+}
+
+async fn trades(manager: web::Data<Manager>) -> impl Responder {
+    HttpResponse::Ok().json(manager.trades())
+}
+
+async fn events(manager: web::Data<Manager>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .streaming(event_stream(manager.get_ref()))
+}
+
+#[derive(Deserialize)]
+struct RouteQuery {
+    max_cargo: f32,
+    max_budget: f32,
+}
+
+async fn plan_route(query: web::Query<RouteQuery>, manager: web::Data<Manager>) -> HttpResponse {
+    match manager.plan_and_set_route(query.max_cargo, query.max_budget).await {
+        Ok(Some(plan)) => HttpResponse::Ok().json(plan),
+        Ok(None) => HttpResponse::Ok().body("No profitable route found"),
+        Err(e) => HttpResponse::InternalServerError().body(format!("Error: {}", e)),
+    }
+}
+
+#[derive(Deserialize)]
+struct HaulQuery {
+    cargo_m3: f32,
+    budget_isk: f32,
+}
+
+async fn plan_haul(query: web::Query<HaulQuery>, manager: web::Data<Manager>) -> HttpResponse {
+    match manager.plan_and_set_haul(query.cargo_m3, query.budget_isk).await {
+        Ok(Some(plan)) => HttpResponse::Ok().json(plan),
+        Ok(None) => HttpResponse::Ok().body("No profitable haul found"),
+        Err(e) => HttpResponse::InternalServerError().body(format!("Error: {}", e)),
+    }
 }
 
 async fn start_oauth(manager: web::Data<Manager>) -> HttpResponse {
@@ -59,8 +160,16 @@ async fn oauth_callback(
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    let manager = web::Data::new(Manager::new());
-
+    let manager = web::Data::new(Manager::new().await);
+
+    // `cargo run -- backfill` replays stored order snapshots through
+    // `create_trades` and exits, instead of starting the server.
+    if std::env::args().any(|arg| arg == "backfill") {
+        return match manager.backfill_trades().await {
+            Ok(()) => Ok(()),
+            Err(e) => Err(std::io::Error::new(std::io::ErrorKind::Other, e.to_string())),
+        };
+    }
 
     let manager_clone = manager.clone();
     let (shutdown_tx, shutdown_rx) = watch::channel(false);
@@ -75,6 +184,12 @@ async fn main() -> std::io::Result<()> {
             .route("/", web::get().to(index))
             .route("/location", web::get().to(location))
             .route("/orders", web::get().to(orders))
+            .route("/events", web::get().to(events))
+            .route("/search", web::get().to(search))
+            .route("/history", web::get().to(history))
+            .route("/trades", web::get().to(trades))
+            .route("/route", web::get().to(plan_route))
+            .route("/haul", web::get().to(plan_haul))
             .route("/start_oauth", web::get().to(start_oauth))
             .route("/oauth_callback", web::get().to(oauth_callback))
     })
@@ -1,75 +1,215 @@
+use std::collections::HashMap;
 use std::sync::Mutex;
 use tokio::sync::watch;
 
 use std::error::Error;
+use std::time::Duration;
+
+use chrono::Utc;
+use rust_decimal::Decimal;
 
 use crate::eve_service::EveService;
-use crate::pathfinding::{Location, build};
-use crate::market::{Order, Orders};
+use crate::items::{self, ItemType};
+use crate::pathfinding::{build, Location, PathfindingData};
+use crate::market::{MarketHistory, Order, OrderFilter, Orders, OrdersPage, SearchHit, SearchIndex, Trade, TypeHistory};
+use crate::route::{self, HaulPlan, RoutePlan};
+use crate::storage::Storage;
+
+const DEFAULT_SEARCH_LIMIT: usize = 20;
+
+// Station standings vary this per-character; ESI has no "current broker
+// fee" endpoint wired up yet, so fall back to the default NPC-station rate.
+//
+// Also used by `backfill_trades` (the `backfill` CLI command in `main`),
+// which replays stored order snapshots through `Trade::create_trades` and
+// needs the same rate.
+pub(crate) const BROKER_FEE_RATE: (i64, u32) = (3, 2); // 0.03
+
+// Fallback cadence when there's nothing to derive a wake-up time from yet
+// (e.g. before the first successful order fetch).
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(10);
+const LOCATION_POLL_INTERVAL: Duration = Duration::from_secs(10);
 
 pub struct Manager {
     // route_state: Mutex<Route>
     // route_info_state: Mutex<RouteInfo>
-    orders_state: Mutex<Orders>,
-    location: Mutex<Option<Location>>,
+    orders_tx: watch::Sender<Orders>,
+    orders_rx: watch::Receiver<Orders>,
+    history_tx: watch::Sender<MarketHistory>,
+    history_rx: watch::Receiver<MarketHistory>,
+    location_tx: watch::Sender<Option<Location>>,
+    location_rx: watch::Receiver<Option<Location>>,
     // log_state: Mutex<Log>
     eve_service: EveService,
-    
+    search_index: Mutex<SearchIndex>,
+    pathfinding: PathfindingData,
+    item_types: HashMap<u64, ItemType>,
+    storage: Storage,
+
 }
 
 impl Manager {
-    pub fn new() -> Manager {
+    pub async fn new() -> Manager {
 
-        build();
+        let pathfinding = build();
+        let item_types = items::parse_item_types().expect("item types to load from SDE");
+        let storage = Storage::new().await.expect("Manager to connect to Postgres");
 
-        Manager {
-            location: Mutex::new( None ),
+        let (orders_tx, orders_rx) = watch::channel(Orders::new());
+        let (history_tx, history_rx) = watch::channel(MarketHistory::new());
+        let (location_tx, location_rx) = watch::channel(None);
+
+        let manager = Manager {
+            location_tx,
+            location_rx,
             eve_service: EveService::new().expect("Manager to generate EveService"),
-            orders_state: Mutex::new( Orders::new() ),
+            orders_tx,
+            orders_rx,
+            history_tx,
+            history_rx,
+            search_index: Mutex::new(SearchIndex::new(item_types.clone())),
+            pathfinding,
+            item_types,
+            storage,
+        };
+
+        match manager.storage.latest_orders().await {
+            Ok(orders) => {
+                if let Ok(mut search_index) = manager.search_index.lock() {
+                    search_index.rebuild(&orders.orders());
+                }
+                let _ = manager.orders_tx.send(orders);
+            }
+            Err(e) => eprintln!("Error loading startup order snapshot: {}", e),
         }
 
+        manager
     }
 
     pub async fn run(&self, shutdown_signal: watch::Receiver<bool>) {
+        let mut next_location_poll = Utc::now();
+
         loop {
-            tokio::time::sleep(tokio::time::Duration::from_secs(10)).await;
             if *shutdown_signal.borrow() {
                 println!("Manager task is shutting down.");
                 break;
             }
-    
-            match self.eve_service.location().await {
-                Ok(result) => {
-                    match self.location.lock() {
-                        Ok(mut location) => *location = Some(result),
-                        Err(e) => eprintln!("Error locking location mutex: {}", e),
+
+            if Utc::now() >= next_location_poll {
+                match self.eve_service.location().await {
+                    Ok(result) => {
+                        self.location_tx.send_if_modified(|current| {
+                            let changed = current.as_ref() != Some(&result);
+                            if changed {
+                                *current = Some(result);
+                            }
+                            changed
+                        });
+                    }
+                    Err(e) => {
+                        eprintln!("Error fetching location {}", e);
                     }
                 }
-                Err(e) => {
-                    eprintln!("Error fetching location {}", e);
-                }
+                next_location_poll = Utc::now() + chrono::Duration::from_std(LOCATION_POLL_INTERVAL).unwrap();
             }
 
-            
-            let orders_clone = match self.orders_state.lock() {
-                Ok(orders_lock) => orders_lock.clone(),
-                Err(e) => {
-                    eprintln!("Error locking orders_state mutex: {}", e);
-                    continue;
-                }
-            };
-    
+            let orders_clone = self.orders_rx.borrow().clone();
+
             match self.eve_service.updated_orders(&orders_clone).await {
                 Ok(updated_orders) => {
-                    match self.orders_state.lock() {
-                        Ok(mut orders_state) => orders_state.update(updated_orders),
-                        Err(e) => eprintln!("Error locking orders_state mutex: {}", e),
+                    if let Err(e) = self.persist_order_snapshot(&updated_orders).await {
+                        eprintln!("Error persisting order snapshot: {}", e);
+                    }
+
+                    let mut orders = orders_clone;
+                    orders.update(updated_orders);
+
+                    if let Ok(mut search_index) = self.search_index.lock() {
+                        search_index.rebuild(&orders.orders());
+                    }
+
+                    let history_clone = self.history_rx.borrow().clone();
+                    match self.eve_service.updated_market_history(&history_clone, &orders).await {
+                        Ok(updated_history) => {
+                            let mut history = history_clone;
+                            history.update(updated_history);
+                            let _ = self.history_tx.send(history);
+                        }
+                        Err(e) => eprintln!("Error updating market history: {}", e),
+                    }
+
+                    self.orders_tx.send_if_modified(|current| {
+                        let changed = *current != orders;
+                        if changed {
+                            *current = orders;
+                        }
+                        changed
+                    });
+
+                    if let Err(e) = self.persist_trade_snapshot().await {
+                        eprintln!("Error persisting trade snapshot: {}", e);
                     }
                 }
                 Err(e) => eprintln!("Error updating orders: {}", e),
             }
+
+            let sleep_for = self.next_wake_after(next_location_poll);
+            tokio::time::sleep(sleep_for).await;
+        }
+    }
+
+    /// How long to sleep before the next loop iteration: wake in time for
+    /// whichever comes first, the next region's order or market-history
+    /// expiry or the next scheduled location poll, instead of a fixed timer.
+    fn next_wake_after(&self, next_location_poll: chrono::DateTime<Utc>) -> Duration {
+        let now = Utc::now();
+
+        let fallback = now + chrono::Duration::from_std(DEFAULT_POLL_INTERVAL).unwrap();
+        let next_orders_poll = self.orders_rx.borrow().next_expiry().unwrap_or(fallback);
+        let next_history_poll = self.history_rx.borrow().next_expiry().unwrap_or(fallback);
+
+        let next_wake = next_orders_poll.min(next_history_poll).min(next_location_poll);
+
+        (next_wake - now)
+            .to_std()
+            .unwrap_or(Duration::from_secs(0))
+            .max(Duration::from_millis(100))
+            .min(DEFAULT_POLL_INTERVAL * 6)
+    }
+
+    /// Writes every region touched by this poll's order refresh to
+    /// `storage`, keyed by `(region_id, order_id, observed_at)`.
+    /// `updated_orders` is the diff `eve_service.updated_orders` returned,
+    /// not the merged book, so an unchanged (304'd) region is re-persisted
+    /// too — the snapshot table is a point-in-time audit log, not a cache.
+    async fn persist_order_snapshot(&self, updated_orders: &Orders) -> Result<(), Box<dyn Error>> {
+        let observed_at = Utc::now();
+
+        for region in updated_orders.regions() {
+            let region_orders = updated_orders.region_orders(region);
+            let expiry = updated_orders.expiry(region).unwrap_or(observed_at);
+            self.storage.insert_order_snapshot(region, &region_orders, observed_at, expiry).await?;
         }
+
+        Ok(())
+    }
+
+    /// Persists the trades derivable from the just-updated order book and
+    /// history, tagged with the time they were computed.
+    async fn persist_trade_snapshot(&self) -> Result<(), Box<dyn Error>> {
+        let observed_at = Utc::now();
+        self.storage.insert_trade_snapshot(&self.trades(), observed_at).await
+    }
+
+    /// Replays every stored order snapshot through `Trade::create_trades`,
+    /// reconstructing and persisting historical trade opportunities. Meant
+    /// to be run as a one-off CLI command (see `main`'s `backfill` arg),
+    /// not from the poll loop.
+    pub async fn backfill_trades(&self) -> Result<(), Box<dyn Error>> {
+        let broker_fee_rate = Decimal::new(BROKER_FEE_RATE.0, BROKER_FEE_RATE.1);
+        self.storage.backfill_trades(broker_fee_rate, &self.item_types).await
     }
+
     pub fn get_authorization_url(&self) -> String {
         self.eve_service.get_authorization_url()
     }
@@ -79,12 +219,110 @@ impl Manager {
     }
 
     pub fn location(&self) -> Option<Location> {
-        let location = self.location.lock().unwrap();
-        location.clone()
+        self.location_rx.borrow().clone()
     }
 
     pub fn orders(&self) -> Vec<Order> {
-        let orders = self.orders_state.lock().unwrap();
-        orders.orders()
+        self.orders_rx.borrow().orders()
+    }
+
+    /// Cursor-paginated, filterable view of the order book, bounded to
+    /// `limit` results per call instead of returning the entire book.
+    pub fn orders_page(&self, filter: &OrderFilter, limit: usize, cursor: Option<&str>) -> OrdersPage {
+        self.orders_rx.borrow().page(filter, limit, cursor)
     }
-}
\ No newline at end of file
+
+    /// Typo-tolerant search over the live order book, matching on station
+    /// or item name (prefix first, then bounded edit distance) or an exact
+    /// `type_id`/`location_id`.
+    pub fn search(&self, query: &str) -> Vec<SearchHit> {
+        self.search_index.lock().unwrap().search(query, DEFAULT_SEARCH_LIMIT)
+    }
+
+    /// Every tracked `type_id`'s candle series and rolling liquidity stats,
+    /// for the `/history` charting endpoint.
+    pub fn market_history(&self) -> HashMap<u64, TypeHistory> {
+        self.history_rx.borrow().by_type()
+    }
+
+    /// Profitable buy/sell pairs across the live order book, pruned to
+    /// trades the market can actually absorb at its recent daily volume.
+    pub fn trades(&self) -> Vec<Trade> {
+        let orders = self.orders();
+        let sell_orders: Vec<Order> = orders.iter().filter(|order| !order.is_buy_order()).cloned().collect();
+        let buy_orders: Vec<Order> = orders.iter().filter(|order| order.is_buy_order()).cloned().collect();
+        let history = self.history_rx.borrow().by_type();
+        let broker_fee_rate = Decimal::new(BROKER_FEE_RATE.0, BROKER_FEE_RATE.1);
+
+        Trade::create_trades(&sell_orders, &buy_orders, broker_fee_rate, &history, &self.item_types)
+    }
+
+    /// Ties the gate graph to the knapsack solver to find the best-profit
+    /// hauling run from the character's current location, then pushes the
+    /// chosen waypoints into ESI's in-game autopilot.
+    pub async fn plan_and_set_route(&self, max_cargo: f32, max_budget: f32) -> Result<Option<RoutePlan>, Box<dyn Error>> {
+        let Some(location) = self.location() else {
+            return Ok(None);
+        };
+
+        let orders = self.orders();
+        let plan = route::plan_route(
+            &location,
+            &self.pathfinding.graph,
+            &self.pathfinding.stations,
+            &orders,
+            &self.item_types,
+            max_cargo,
+            max_budget,
+        );
+
+        if let Some(plan) = &plan {
+            self.eve_service.set_waypoints(&plan.waypoints).await?;
+        }
+
+        Ok(plan)
+    }
+
+    /// Cargo- and budget-constrained alternative to `plan_and_set_route`:
+    /// greedily fills the hold with the best profit-per-m³ basket instead
+    /// of running `plan_route`'s discretized knapsack.
+    pub async fn plan_and_set_haul(&self, cargo_m3: f32, budget_isk: f32) -> Result<Option<HaulPlan>, Box<dyn Error>> {
+        let Some(location) = self.location() else {
+            return Ok(None);
+        };
+
+        let orders = self.orders();
+        let history = self.history_rx.borrow().by_type();
+        let broker_fee_rate = Decimal::new(BROKER_FEE_RATE.0, BROKER_FEE_RATE.1);
+
+        let plan = route::plan_haul(
+            &location,
+            &self.pathfinding.graph,
+            &self.pathfinding.stations,
+            &orders,
+            &history,
+            &self.item_types,
+            broker_fee_rate,
+            cargo_m3,
+            budget_isk,
+        );
+
+        if let Some(plan) = &plan {
+            self.eve_service.set_waypoints(&plan.waypoints).await?;
+        }
+
+        Ok(plan)
+    }
+
+    /// Yields only when the tracked location changes, instead of requiring
+    /// clients to poll `location()`.
+    pub fn subscribe_location(&self) -> watch::Receiver<Option<Location>> {
+        self.location_tx.subscribe()
+    }
+
+    /// Yields only when the order book changes, instead of requiring
+    /// clients to poll `orders()`.
+    pub fn subscribe_orders(&self) -> watch::Receiver<Orders> {
+        self.orders_tx.subscribe()
+    }
+}
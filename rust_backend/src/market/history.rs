@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::market::isk::{deserialize_f64, Isk};
+
+// Kept separate from `order::REGIONS` so each region-polling module can
+// evolve its own cadence independently, even though today they agree.
+const REGIONS: [u64; 1] = [10000033];
+
+const HISTORY_WINDOW_DAYS: usize = 30;
+
+/// One ESI `/markets/{region_id}/history/` day: one of these per day per
+/// `type_id`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Candle {
+    pub date: NaiveDate,
+    #[serde(deserialize_with = "deserialize_f64")]
+    pub highest: Isk,
+    #[serde(deserialize_with = "deserialize_f64")]
+    pub lowest: Isk,
+    #[serde(deserialize_with = "deserialize_f64")]
+    pub average: Isk,
+    pub order_count: u64,
+    pub volume: u64,
+}
+
+/// A `type_id`'s candle series plus the rolling 30-day stats that
+/// `Trade::create_trades` uses to prune illiquid trades.
+#[derive(Debug, Clone, Serialize)]
+pub struct TypeHistory {
+    pub candles: Vec<Candle>,
+    pub avg_daily_volume: f64,
+    pub avg_price: Isk,
+}
+
+impl TypeHistory {
+    fn from_candles(mut candles: Vec<Candle>) -> Self {
+        candles.sort_by_key(|candle| candle.date);
+
+        let window: Vec<&Candle> = candles.iter().rev().take(HISTORY_WINDOW_DAYS).collect();
+
+        if window.is_empty() {
+            return TypeHistory { candles, avg_daily_volume: 0.0, avg_price: Isk::ZERO };
+        }
+
+        let avg_daily_volume = window.iter().map(|candle| candle.volume as f64).sum::<f64>() / window.len() as f64;
+        let total_price = window.iter().fold(Isk::ZERO, |total, candle| total + candle.average);
+        let avg_price = total_price / window.len() as u64;
+
+        TypeHistory { candles, avg_daily_volume, avg_price }
+    }
+}
+
+/// Cached market history, region by region, in the same shape `Orders`
+/// caches the order book: a per-region snapshot plus its own expiry so the
+/// poll loop only refetches once ESI's daily update has landed.
+#[derive(Clone, Serialize)]
+pub struct MarketHistory {
+    region_history: HashMap<u64, HashMap<u64, TypeHistory>>,
+    expiry_times: HashMap<u64, DateTime<Utc>>,
+}
+
+impl MarketHistory {
+    pub fn new() -> Self {
+        MarketHistory {
+            region_history: HashMap::new(),
+            expiry_times: HashMap::new(),
+        }
+    }
+
+    pub fn insert(&mut self, region: u64, candles_by_type: HashMap<u64, Vec<Candle>>, expiry: DateTime<Utc>) {
+        let history = candles_by_type
+            .into_iter()
+            .map(|(type_id, candles)| (type_id, TypeHistory::from_candles(candles)))
+            .collect();
+
+        self.region_history.insert(region, history);
+        self.expiry_times.insert(region, expiry);
+    }
+
+    pub fn expired_regions(&self) -> Vec<u64> {
+        let current_time = Utc::now();
+
+        let mut expired: Vec<u64> = self.expiry_times.iter()
+            .filter(|&(_region_id, &expiry_time)| expiry_time < current_time)
+            .map(|(&region_id, _)| region_id)
+            .collect();
+
+        for &region in REGIONS.iter() {
+            if !self.expiry_times.contains_key(&region) {
+                expired.push(region);
+            }
+        }
+
+        expired
+    }
+
+    pub fn update(&mut self, updated_history: MarketHistory) {
+        for (key, value) in updated_history.region_history {
+            self.region_history.insert(key, value);
+
+            if let Some(expiry_time) = updated_history.expiry_times.get(&key) {
+                self.expiry_times.insert(key, *expiry_time);
+            }
+        }
+    }
+
+    /// The next time any region's history snapshot goes stale, mirroring
+    /// `Orders::next_expiry` so the poll loop can fold both into one
+    /// wake-up calculation.
+    pub fn next_expiry(&self) -> Option<DateTime<Utc>> {
+        if REGIONS.iter().any(|region| !self.expiry_times.contains_key(region)) {
+            return None;
+        }
+
+        self.expiry_times.values().min().copied()
+    }
+
+    /// Every tracked `type_id`'s history, merged across regions. Backs both
+    /// the `/history` charting endpoint and `Trade::create_trades`'s
+    /// liquidity pruning.
+    pub fn by_type(&self) -> HashMap<u64, TypeHistory> {
+        self.region_history.values().flat_map(|by_type| by_type.clone()).collect()
+    }
+}
@@ -0,0 +1,153 @@
+use std::fmt;
+use std::ops::{Add, Div, Mul, Sub};
+use std::str::FromStr;
+
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// An exact ISK amount backed by a fixed-point decimal instead of `f64`, so
+/// profit totals summed across thousands of orders don't drift the way
+/// binary floating point would. Arithmetic (`Add`/`Sub`/`Mul`/`Div`) stays
+/// full-precision; rounding only happens explicitly, at the point of display
+/// (see `round`, used by this type's own `Serialize` impl).
+///
+/// Serializes as a decimal string rather than a JSON number so API responses
+/// don't pick up binary-float noise on the way out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Isk(Decimal);
+
+impl Isk {
+    pub const ZERO: Isk = Isk(Decimal::ZERO);
+
+    pub fn from_f64(value: f64) -> Self {
+        use rust_decimal::prelude::FromPrimitive;
+        Isk(Decimal::from_f64(value).unwrap_or(Decimal::ZERO))
+    }
+
+    pub fn to_f64(self) -> f64 {
+        use rust_decimal::prelude::ToPrimitive;
+        self.0.to_f64().unwrap_or(0.0)
+    }
+
+    pub fn is_positive(self) -> bool {
+        self.0 > Decimal::ZERO
+    }
+
+    /// Rounds half up (away from zero) to `dp` decimal places for display.
+    pub fn round(self, dp: u32) -> Decimal {
+        self.0.round_dp_with_strategy(dp, RoundingStrategy::MidpointAwayFromZero)
+    }
+}
+
+impl Add for Isk {
+    type Output = Isk;
+    fn add(self, rhs: Isk) -> Isk {
+        Isk(self.0 + rhs.0)
+    }
+}
+
+impl Sub for Isk {
+    type Output = Isk;
+    fn sub(self, rhs: Isk) -> Isk {
+        Isk(self.0 - rhs.0)
+    }
+}
+
+impl Mul<Decimal> for Isk {
+    type Output = Isk;
+    fn mul(self, rhs: Decimal) -> Isk {
+        Isk(self.0 * rhs)
+    }
+}
+
+impl Mul<u64> for Isk {
+    type Output = Isk;
+    fn mul(self, rhs: u64) -> Isk {
+        Isk(self.0 * Decimal::from(rhs))
+    }
+}
+
+impl Div<u64> for Isk {
+    type Output = Isk;
+    fn div(self, rhs: u64) -> Isk {
+        Isk(self.0 / Decimal::from(rhs))
+    }
+}
+
+impl fmt::Display for Isk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for Isk {
+    type Err = rust_decimal::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Decimal::from_str(s).map(Isk)
+    }
+}
+
+impl Serialize for Isk {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.round(2).to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Isk {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Decimal::from_str(&s).map(Isk).map_err(de::Error::custom)
+    }
+}
+
+/// ESI sends ISK amounts as JSON numbers (`price`, history's `highest` /
+/// `lowest` / `average`, ...); convert at the network boundary with this
+/// field-level `deserialize_with` rather than via `Isk`'s own `Deserialize`,
+/// which expects the string form used for round-tripping our own output.
+pub(crate) fn deserialize_f64<'de, D>(deserializer: D) -> Result<Isk, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = f64::deserialize(deserializer)?;
+    Ok(Isk::from_f64(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Pins from_f64 to the clean decimal value rather than the noisy binary
+    // expansion Decimal::from_f64_retain would produce for the same input.
+    #[test]
+    fn from_f64_is_exact_not_noisy() {
+        assert_eq!(Isk::from_f64(19.99).to_string(), "19.99");
+        assert_eq!(Isk::from_f64(0.1).to_string(), "0.1");
+    }
+
+    #[test]
+    fn round_rounds_half_up_to_the_given_dp() {
+        assert_eq!(Isk::from_f64(19.995).round(2).to_string(), "20.00");
+        assert_eq!(Isk::from_f64(19.994).round(2).to_string(), "19.99");
+    }
+
+    #[test]
+    fn display_from_str_roundtrip() {
+        let original = Isk::from_f64(1234.5);
+        let parsed: Isk = original.to_string().parse().unwrap();
+        assert_eq!(original, parsed);
+    }
+
+    // This is what Isk's own Serialize impl relies on to ship display-rounded
+    // values at the API boundary instead of raw unrounded ones.
+    #[test]
+    fn serialize_rounds_to_two_decimal_places() {
+        let json = serde_json::to_string(&Isk::from_f64(19.999)).unwrap();
+        assert_eq!(json, "\"20.00\"");
+    }
+
+    #[test]
+    fn arithmetic_stays_exact_before_rounding() {
+        let total = Isk::from_f64(0.1) + Isk::from_f64(0.2);
+        assert_eq!(total.to_string(), "0.3");
+    }
+}
@@ -0,0 +1,12 @@
+mod history;
+mod isk;
+mod order;
+mod search;
+mod trade;
+
+pub use history::{Candle, MarketHistory, TypeHistory};
+pub use isk::Isk;
+pub use order::{Order, OrderFilter, Orders, OrdersPage};
+pub use search::{SearchHit, SearchIndex};
+pub use trade::Trade;
+pub(crate) use trade::allocate_matches;
@@ -5,22 +5,46 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 use crate::eve_service::EveService;
+use crate::market::isk::{deserialize_f64, Isk};
 
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Order {
-    duration: u64,
-    is_buy_order: bool,
-    issued: DateTime<Utc>,
-    location_id: u64,
-    min_volume: u64,
-    order_id: u64,
-    price: f64,
-    range: String,
-    system_id: u64,
-    type_id: u64,
-    volume_remain: u64,
-    volume_total: u64
+    pub(crate) duration: u64,
+    pub(crate) is_buy_order: bool,
+    pub(crate) issued: DateTime<Utc>,
+    pub(crate) location_id: u64,
+    pub(crate) min_volume: u64,
+    pub(crate) order_id: u64,
+    #[serde(deserialize_with = "deserialize_f64")]
+    pub(crate) price: Isk,
+    pub(crate) range: String,
+    pub(crate) system_id: u64,
+    pub(crate) type_id: u64,
+    pub(crate) volume_remain: u64,
+    pub(crate) volume_total: u64
+}
+
+impl Order {
+    pub fn location_id(&self) -> u64 {
+        self.location_id
+    }
+
+    pub fn type_id(&self) -> u64 {
+        self.type_id
+    }
+
+    pub fn volume_remain(&self) -> u64 {
+        self.volume_remain
+    }
+
+    pub fn is_buy_order(&self) -> bool {
+        self.is_buy_order
+    }
+
+    pub fn price(&self) -> Isk {
+        self.price
+    }
 }
 
 const REGIONS: [u64; 1] = [10000033];
@@ -29,6 +53,17 @@ const REGIONS: [u64; 1] = [10000033];
 pub struct Orders {
     region_orders: HashMap<u64, Vec<Order>>,
     expiry_times: HashMap<u64, DateTime<Utc>>, // region_id to expiration time
+    etags: HashMap<u64, String>, // region_id to the ETag its last response carried
+}
+
+impl PartialEq for Orders {
+    /// Compares only the order book itself, not `expiry_times`/`etags` —
+    /// those bump on every poll (even a `304 Not Modified` refreshes the
+    /// expiry) so including them would defeat `send_if_modified`'s whole
+    /// point of only waking subscribers when the book actually changes.
+    fn eq(&self, other: &Self) -> bool {
+        self.region_orders == other.region_orders
+    }
 }
 
 
@@ -37,12 +72,44 @@ impl Orders {
         Orders {
             region_orders: HashMap::new(),
             expiry_times: HashMap::new(),
+            etags: HashMap::new(),
         }
     }
 
-    pub fn insert(&mut self, region: u64, orders: Vec<Order>, expiry: DateTime<Utc>) {
+    pub fn insert(&mut self, region: u64, orders: Vec<Order>, expiry: DateTime<Utc>, etag: Option<String>) {
         self.region_orders.insert(region, orders);
         self.expiry_times.insert(region, expiry);
+
+        match etag {
+            Some(etag) => { self.etags.insert(region, etag); }
+            None => { self.etags.remove(&region); }
+        }
+    }
+
+    /// The region's cached orders, for reuse when a conditional request
+    /// comes back `304 Not Modified`. Empty if the region hasn't been
+    /// fetched yet.
+    pub fn region_orders(&self, region: u64) -> Vec<Order> {
+        self.region_orders.get(&region).cloned().unwrap_or_default()
+    }
+
+    /// The `ETag` the region's last response carried, sent back as
+    /// `If-None-Match` so an unchanged order book comes back as a cheap
+    /// `304` instead of the full page set.
+    pub fn etag(&self, region: u64) -> Option<&str> {
+        self.etags.get(&region).map(String::as_str)
+    }
+
+    /// Every region currently tracked, for callers (e.g. `storage`) that
+    /// need to persist a snapshot region by region instead of as one
+    /// flattened list.
+    pub fn regions(&self) -> Vec<u64> {
+        self.region_orders.keys().copied().collect()
+    }
+
+    /// The region's cached expiry, if it's been fetched at least once.
+    pub fn expiry(&self, region: u64) -> Option<DateTime<Utc>> {
+        self.expiry_times.get(&region).copied()
     }
 
     pub fn expired_regions(&self) -> Vec<u64> {
@@ -73,6 +140,11 @@ impl Orders {
             if let Some(expiry_time) = updated_orders.expiry_times.get(&key) {
                 self.expiry_times.insert(key, *expiry_time);
             }
+
+            match updated_orders.etags.get(&key) {
+                Some(etag) => { self.etags.insert(key, etag.clone()); }
+                None => { self.etags.remove(&key); }
+            }
         }
     }
 
@@ -80,4 +152,140 @@ impl Orders {
     pub fn orders(&self) -> Vec<Order> {
         self.region_orders.values().flat_map(|orders| orders.clone()).collect()
     }
+
+    /// The next time any region's cached page goes stale, so the poll loop
+    /// can wake exactly then instead of on a blind timer. `None` means a
+    /// tracked region has no expiry yet (e.g. never fetched) and should be
+    /// refreshed immediately.
+    pub fn next_expiry(&self) -> Option<DateTime<Utc>> {
+        if REGIONS.iter().any(|region| !self.expiry_times.contains_key(region)) {
+            return None;
+        }
+
+        self.expiry_times.values().min().copied()
+    }
+
+    /// A stable slice of the order book sorted by `(type_id, price,
+    /// order_id)`, so repeated calls return consistent pages regardless of
+    /// `HashMap` iteration order.
+    pub fn range(&self, start: usize, limit: usize) -> Vec<Order> {
+        let mut sorted = self.orders();
+        sorted.sort_by(sort_key_cmp);
+        sorted.into_iter().skip(start).take(limit).collect()
+    }
+
+    /// Cursor-paginated, filterable view over the order book, in the style
+    /// of Garage's K2V range API: apply `filter`, sort stably, and return a
+    /// page plus an opaque `next_cursor` encoding the last-seen sort key so
+    /// the caller can resume from exactly where they left off.
+    pub fn page(&self, filter: &OrderFilter, limit: usize, cursor: Option<&str>) -> OrdersPage {
+        let mut filtered = self.filtered_orders(filter);
+        filtered.sort_by(sort_key_cmp);
+
+        let start = match cursor.and_then(decode_cursor) {
+            Some(after) => filtered
+                .iter()
+                .position(|order| sort_key(order) > after)
+                .unwrap_or(filtered.len()),
+            None => 0,
+        };
+
+        let page: Vec<Order> = filtered[start..].iter().take(limit).cloned().collect();
+        let next_cursor = if start + page.len() < filtered.len() {
+            page.last().map(encode_cursor)
+        } else {
+            None
+        };
+
+        OrdersPage { orders: page, next_cursor }
+    }
+
+    fn filtered_orders(&self, filter: &OrderFilter) -> Vec<Order> {
+        let regions: Vec<u64> = match filter.region {
+            Some(region) => vec![region],
+            None => self.region_orders.keys().copied().collect(),
+        };
+
+        let mut orders: Vec<Order> = regions
+            .iter()
+            .filter_map(|region| self.region_orders.get(region))
+            .flat_map(|orders| orders.iter().cloned())
+            .filter(|order| filter.type_id.map_or(true, |type_id| order.type_id == type_id))
+            .collect();
+
+        if let Some(min_profit) = filter.min_profit {
+            let min_profit = Isk::from_f64(min_profit);
+            let best_opposite_price = best_opposite_price_by_type(&orders);
+            orders.retain(|order| {
+                order_profit(order, &best_opposite_price).map_or(false, |profit| profit >= min_profit)
+            });
+        }
+
+        orders
+    }
+}
+
+/// Per `type_id`, the best opposing price available: the highest buy-order
+/// price an order could sell into, and the lowest sell-order price an order
+/// could buy from. Used to estimate `min_profit` filtering on a single
+/// `Order` without needing a full `Trade`.
+fn best_opposite_price_by_type(orders: &[Order]) -> HashMap<u64, (Option<Isk>, Option<Isk>)> {
+    let mut best: HashMap<u64, (Option<Isk>, Option<Isk>)> = HashMap::new();
+
+    for order in orders {
+        let entry = best.entry(order.type_id).or_insert((None, None));
+        if order.is_buy_order {
+            entry.0 = Some(entry.0.map_or(order.price, |best| best.max(order.price)));
+        } else {
+            entry.1 = Some(entry.1.map_or(order.price, |best| best.min(order.price)));
+        }
+    }
+
+    best
+}
+
+fn order_profit(order: &Order, best_opposite_price: &HashMap<u64, (Option<Isk>, Option<Isk>)>) -> Option<Isk> {
+    let &(best_buy, best_sell) = best_opposite_price.get(&order.type_id)?;
+
+    if order.is_buy_order {
+        best_sell.map(|best_sell| order.price - best_sell)
+    } else {
+        best_buy.map(|best_buy| best_buy - order.price)
+    }
+}
+
+fn sort_key_cmp(a: &Order, b: &Order) -> std::cmp::Ordering {
+    sort_key(a).cmp(&sort_key(b))
+}
+
+/// `(type_id, price, order_id)` — a total order over `Order` that's stable
+/// across pages.
+fn sort_key(order: &Order) -> (u64, Isk, u64) {
+    (order.type_id, order.price, order.order_id)
+}
+
+fn encode_cursor(order: &Order) -> String {
+    let (type_id, price, order_id) = sort_key(order);
+    format!("{}:{}:{}", type_id, price, order_id)
+}
+
+fn decode_cursor(cursor: &str) -> Option<(u64, Isk, u64)> {
+    let mut parts = cursor.split(':');
+    let type_id = parts.next()?.parse().ok()?;
+    let price: Isk = parts.next()?.parse().ok()?;
+    let order_id = parts.next()?.parse().ok()?;
+    Some((type_id, price, order_id))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct OrderFilter {
+    pub region: Option<u64>,
+    pub type_id: Option<u64>,
+    pub min_profit: Option<f64>,
+}
+
+#[derive(Serialize)]
+pub struct OrdersPage {
+    pub orders: Vec<Order>,
+    pub next_cursor: Option<String>,
 }
\ No newline at end of file
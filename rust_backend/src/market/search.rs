@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::items::ItemType;
+use crate::market::Order;
+use crate::pathfinding::{parse_stations, MapNode};
+
+// Matches beyond this many edits are treated as noise rather than a typo.
+const MAX_EDIT_DISTANCE: usize = 2;
+
+#[derive(Serialize, Clone)]
+pub struct SearchHit {
+    pub order: Order,
+    pub station: Option<MapNode>,
+}
+
+/// In-memory, typo-tolerant index over the live order book, station names,
+/// and item names — a query matches if it's a fuzzy hit on the order's
+/// station name *or* its item name, or an exact `type_id`/`location_id`.
+///
+/// Station names come from the SDE (`mapDenormalize.csv`, loaded once at
+/// startup); item names come from the `item_types` table the caller already
+/// loaded via `items::parse_item_types` (so the SDE's `invTypes.csv` is only
+/// parsed once, not duplicated here).
+pub struct SearchIndex {
+    stations_by_id: HashMap<u64, MapNode>,
+    item_types_by_id: HashMap<u64, ItemType>,
+    orders_by_location: HashMap<u64, Vec<Order>>,
+}
+
+impl SearchIndex {
+    pub fn new(item_types_by_id: HashMap<u64, ItemType>) -> Self {
+        let stations_by_id = parse_stations()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|station| (station.item_id, station))
+            .collect();
+
+        SearchIndex {
+            stations_by_id,
+            item_types_by_id,
+            orders_by_location: HashMap::new(),
+        }
+    }
+
+    /// Rebuild the order-book side of the index. Called every time
+    /// `Orders::update` runs in `Manager` so a search always reflects the
+    /// latest snapshot.
+    pub fn rebuild(&mut self, orders: &[Order]) {
+        let mut orders_by_location: HashMap<u64, Vec<Order>> = HashMap::new();
+        for order in orders {
+            orders_by_location
+                .entry(order.location_id())
+                .or_default()
+                .push(order.clone());
+        }
+        self.orders_by_location = orders_by_location;
+    }
+
+    pub fn search(&self, query: &str, limit: usize) -> Vec<SearchHit> {
+        let query = query.trim().to_lowercase();
+        if query.is_empty() {
+            return Vec::new();
+        }
+
+        let query_id = query.parse::<u64>().ok();
+
+        let mut ranked: Vec<(u32, usize, SearchHit)> = Vec::new();
+
+        for (location_id, orders) in &self.orders_by_location {
+            let station = self.stations_by_id.get(location_id);
+            let station_rank = station.and_then(|station| name_rank(&query, &station.name));
+            let location_exact = query_id == Some(*location_id);
+
+            for order in orders {
+                let item_name = self.item_types_by_id.get(&order.type_id()).map(|item| item.name.as_str());
+                let item_rank = item_name.and_then(|name| name_rank(&query, name));
+                let type_id_exact = query_id == Some(order.type_id());
+
+                let rank = [
+                    station_rank,
+                    item_rank,
+                    location_exact.then_some((0, 0)),
+                    type_id_exact.then_some((0, 0)),
+                ].into_iter().flatten().min();
+
+                let Some(rank) = rank else { continue };
+
+                ranked.push((
+                    rank.0,
+                    rank.1,
+                    SearchHit {
+                        order: order.clone(),
+                        station: station.cloned(),
+                    },
+                ));
+            }
+        }
+
+        ranked.sort_by(|a, b| {
+            a.0.cmp(&b.0)
+                .then(a.1.cmp(&b.1))
+                .then(b.2.order.volume_remain().cmp(&a.2.order.volume_remain()))
+        });
+
+        ranked.into_iter().take(limit).map(|(_, _, hit)| hit).collect()
+    }
+}
+
+/// `(exact_prefix_rank, edit_distance)`, lower is better; `None` if the name
+/// doesn't match within the edit-distance budget at all.
+fn name_rank(query: &str, name: &str) -> Option<(u32, usize)> {
+    let name_lower = name.to_lowercase();
+
+    if name_lower.starts_with(query.as_str()) {
+        return Some((0, 0));
+    }
+
+    let distance = bounded_levenshtein(query, &name_lower, MAX_EDIT_DISTANCE)?;
+    Some((1, distance))
+}
+
+/// Levenshtein distance, bailing out early once it's clear the distance will
+/// exceed `max`. Cheap enough to run per-station on every query since the
+/// station count is small relative to the order book.
+fn bounded_levenshtein(a: &str, b: &str, max: usize) -> Option<usize> {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    if a.len().abs_diff(b.len()) > max {
+        return None;
+    }
+
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr = vec![0usize; b.len() + 1];
+
+    for i in 1..=a.len() {
+        curr[0] = i;
+        let mut row_min = curr[0];
+
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            curr[j] = (prev[j] + 1)
+                .min(curr[j - 1] + 1)
+                .min(prev[j - 1] + cost);
+            row_min = row_min.min(curr[j]);
+        }
+
+        if row_min > max {
+            return None;
+        }
+
+        std::mem::swap(&mut prev, &mut curr);
+    }
+
+    let distance = prev[b.len()];
+    if distance <= max {
+        Some(distance)
+    } else {
+        None
+    }
+}
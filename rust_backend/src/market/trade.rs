@@ -1,63 +1,251 @@
-use crate::order::Order;
+use std::collections::HashMap;
 
-pub struct Trade<'a> {
-    from: &'a Order,
-    to: &'a Order,
-    quantity: usize,
-    gross_profit: f64,
-}
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+use crate::items::ItemType;
+use crate::market::history::TypeHistory;
+use crate::market::isk::Isk;
+use crate::market::order::Order;
+
+// ESI sales tax on the sell side of a trade.
+const TAX_RATE: (i64, u32) = (8, 2); // 0.08
 
-const TAX_RATE: f64 = 0.08
+// Candle aggregators prune symbols the market can't actually absorb at a
+// reasonable pace; mirror that instead of reporting "profit" on a trade
+// that would sit half-filled for a month.
+const MAX_DAYS_TO_SELL: f64 = 14.0;
 
-impl<'a> Trade<'a> {
-    pub fn new(from: &'a Order, to: &'a Order, quantity: usize) -> Self {
-        Self { from, to, quantity }
+#[derive(Serialize)]
+pub struct Trade {
+    pub type_id: u64,
+    pub quantity: u64,
+    pub buy_at: u64,
+    pub sell_at: u64,
+    /// Cost to acquire one unit (the sell order's price) — what a
+    /// cargo/budget-constrained basket solver needs per-unit, since
+    /// `gross_profit` is already totalled over `quantity`.
+    pub buy_price: Isk,
+    pub gross_profit: Isk,
+    pub days_to_sell: Option<f64>,
+    /// `quantity * ItemType::volume`, or `None` if the `type_id` isn't in
+    /// the loaded SDE item-type table.
+    pub total_volume_m3: Option<f64>,
+}
+
+impl Trade {
+    fn new(
+        type_id: u64,
+        quantity: u64,
+        buy_at: u64,
+        sell_at: u64,
+        buy_price: Isk,
+        gross_profit: Isk,
+        days_to_sell: Option<f64>,
+        total_volume_m3: Option<f64>,
+    ) -> Self {
+        Self { type_id, quantity, buy_at, sell_at, buy_price, gross_profit, days_to_sell, total_volume_m3 }
     }
 
-    pub fn create_trades(sell_orders: &'a [Order], buy_orders: &'a [Order]) -> Vec<Trade<'a>> {
-        let mut trades: Vec<Trade<'a>> = Vec::new();
-        let mut grouped_orders: HashMap<i32, (Vec<&'a Order>, Vec<&'a Order>)> = HashMap::new();
+    /// `broker_fee_rate` is a second, configurable cut taken off the buy
+    /// side alongside the fixed sales tax (station standings vary it
+    /// per-character, so it isn't baked in as a constant like `TAX_RATE`).
+    ///
+    /// `history` supplies each `type_id`'s rolling average daily volume
+    /// (see `market::history`). Trades that would take longer than
+    /// `MAX_DAYS_TO_SELL` to clear at that pace are dropped rather than
+    /// surfaced as profitable; trades for a `type_id` with no history yet
+    /// are kept with `days_to_sell: None` instead of being penalized for
+    /// missing data.
+    ///
+    /// `item_types` supplies each `type_id`'s packaged volume (see
+    /// `crate::items`) so a trade can report the hold space it needs.
+    pub fn create_trades(
+        sell_orders: &[Order],
+        buy_orders: &[Order],
+        broker_fee_rate: Decimal,
+        history: &HashMap<u64, TypeHistory>,
+        item_types: &HashMap<u64, ItemType>,
+    ) -> Vec<Trade> {
+        let tax_rate = Decimal::new(TAX_RATE.0, TAX_RATE.1);
+        let net_rate = Decimal::ONE - tax_rate - broker_fee_rate;
+
+        let mut trades: Vec<Trade> = Vec::new();
+        let mut grouped_orders: HashMap<u64, (Vec<&Order>, Vec<&Order>)> = HashMap::new();
 
         // Grouping sell orders
         for order in sell_orders {
-            grouped_orders.entry(order.type_id)
-                .or_insert((Vec::new(), Vec::new()))
+            grouped_orders.entry(order.type_id())
+                .or_insert_with(|| (Vec::new(), Vec::new()))
                 .0
                 .push(order);
         }
 
         // Grouping buy orders
         for order in buy_orders {
-            grouped_orders.entry(order.type_id)
-                .or_insert((Vec::new(), Vec::new()))
+            grouped_orders.entry(order.type_id())
+                .or_insert_with(|| (Vec::new(), Vec::new()))
                 .1
                 .push(order);
         }
 
-        // Iterating over each type_id's sell and buy orders to create trades
-        for (_type_id, (sells, buys)) in grouped_orders {
-            for &sell_order in sells {
-                for &buy_order in buys {
-                    let quantity = std::cmp::min(sell_order.volume_remain, buy_order.volume_remain);
-                    let gross_profit = quantity as f64 * (buy_order.price * (1-TAX_RATE) - sell_order.price);
-
-                    if gross_profit <= 0.0 {
-                        continue;
-                    }
-
-                    let trade = Trade::new(
-                        sell_order,  
-                        buy_order,
-                        quantity,
-                        gross_profit,
-                    );
-
-                    trades.push(trade);
+        // Iterating over each type_id's sell and buy orders to create trades.
+        // `allocate_matches` consumes each side's `volume_remain` as quantity
+        // is allocated, so the same unit of stock can't back two trades.
+        for (type_id, (sells, buys)) in grouped_orders {
+            let avg_daily_volume = history.get(&type_id).map(|type_history| type_history.avg_daily_volume);
+
+            let type_trades = allocate_matches(&sells, &buys, |sell_order, buy_order, quantity| {
+                let net_buy_price = buy_order.price() * net_rate;
+                let unit_profit = net_buy_price - sell_order.price();
+                if !unit_profit.is_positive() {
+                    return None;
                 }
-            }
+
+                let days_to_sell = avg_daily_volume
+                    .filter(|&volume| volume > 0.0)
+                    .map(|volume| quantity as f64 / volume);
+
+                if days_to_sell.map_or(false, |days| days > MAX_DAYS_TO_SELL) {
+                    return None;
+                }
+
+                let gross_profit = unit_profit * quantity;
+                let total_volume_m3 = item_types.get(&type_id).map(|item_type| item_type.volume * quantity as f64);
+
+                Some(Trade::new(
+                    type_id,
+                    quantity,
+                    sell_order.location_id(),
+                    buy_order.location_id(),
+                    sell_order.price(),
+                    gross_profit,
+                    days_to_sell,
+                    total_volume_m3,
+                ))
+            });
+
+            trades.extend(type_trades);
         }
 
         trades
     }
+}
+
+/// Matches `sells` against `buys` for a single `type_id`, consuming each
+/// order's `volume_remain` as quantity is allocated so the same unit of
+/// stock on a sell (or buy) order can't back two different results. Sells
+/// are walked cheapest-first against buys best-price-first.
+///
+/// `build` turns a candidate pairing (with the quantity it would allocate)
+/// into the caller's result, or `None` to reject it - e.g. after tax, or
+/// against a liquidity cap. A rejected pairing moves on to the next-best buy
+/// without consuming either side's volume, so the sell's stock is still
+/// available for a pairing `build` does accept. The rejected buy is not
+/// offered to any later (pricier) sell.
+///
+/// Shared by `create_trades` and `route::plan_route` - the two places that
+/// turn a raw order book into buy/sell pairs, and the two places the same
+/// double-counting bug was found.
+pub(crate) fn allocate_matches<'a, T>(
+    sells: &[&'a Order],
+    buys: &[&'a Order],
+    mut build: impl FnMut(&'a Order, &'a Order, u64) -> Option<T>,
+) -> Vec<T> {
+    let mut sells = sells.to_vec();
+    let mut buys = buys.to_vec();
+    sells.sort_by_key(|order| order.price());
+    buys.sort_by_key(|order| std::cmp::Reverse(order.price()));
+
+    let mut sell_remaining: Vec<u64> = sells.iter().map(|order| order.volume_remain()).collect();
+    let mut buy_remaining: Vec<u64> = buys.iter().map(|order| order.volume_remain()).collect();
+
+    let mut results = Vec::new();
+    let mut buy_idx = 0;
+
+    for (sell_idx, &sell_order) in sells.iter().enumerate() {
+        while sell_remaining[sell_idx] > 0 && buy_idx < buys.len() {
+            if buy_remaining[buy_idx] == 0 {
+                buy_idx += 1;
+                continue;
+            }
+
+            let buy_order = buys[buy_idx];
+            if buy_order.price() <= sell_order.price() {
+                // Buys are sorted best-first and sells worst-first, so once
+                // the best remaining buy can't clear this sell, no later
+                // (cheaper) buy will either - this sell is done.
+                break;
+            }
+
+            let quantity = sell_remaining[sell_idx].min(buy_remaining[buy_idx]);
+
+            match build(sell_order, buy_order, quantity) {
+                Some(result) => {
+                    results.push(result);
+                    sell_remaining[sell_idx] -= quantity;
+                    buy_remaining[buy_idx] -= quantity;
+                }
+                None => buy_idx += 1,
+            }
+        }
+    }
+
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
 
+    fn order(is_buy_order: bool, location_id: u64, price: f64, volume_remain: u64) -> Order {
+        Order {
+            duration: 90,
+            is_buy_order,
+            issued: Utc::now(),
+            location_id,
+            min_volume: 1,
+            order_id: location_id,
+            price: Isk::from_f64(price),
+            range: "region".to_string(),
+            system_id: 0,
+            type_id: 34,
+            volume_remain,
+            volume_total: volume_remain,
+        }
+    }
+
+    // Pins the tax/broker-fee arithmetic: gross_profit should reflect the
+    // buy side's price net of both cuts, not the raw spread.
+    #[test]
+    fn create_trades_nets_out_tax_and_broker_fee() {
+        let sells = vec![order(false, 1, 100.0, 10)];
+        let buys = vec![order(true, 2, 150.0, 10)];
+        let broker_fee_rate = Decimal::new(3, 2); // 0.03
+
+        let trades = Trade::create_trades(&sells, &buys, broker_fee_rate, &HashMap::new(), &HashMap::new());
+
+        assert_eq!(trades.len(), 1);
+        let trade = &trades[0];
+        // net_rate = 1 - 0.08 (tax) - 0.03 (broker fee) = 0.89
+        // unit_profit = 150 * 0.89 - 100 = 33.5
+        assert_eq!(trade.quantity, 10);
+        assert_eq!(trade.gross_profit.round(2).to_string(), "335.00");
+    }
+
+    #[test]
+    fn create_trades_does_not_double_count_a_shared_order() {
+        // One sell order and two buy orders for the same type_id, both
+        // wanting more than the sell order actually has - the two resulting
+        // trades must not both claim the sell order's full volume_remain.
+        let sells = vec![order(false, 1, 100.0, 10)];
+        let buys = vec![order(true, 2, 200.0, 10), order(true, 3, 190.0, 10)];
+
+        let trades = Trade::create_trades(&sells, &buys, Decimal::ZERO, &HashMap::new(), &HashMap::new());
+
+        let total_quantity: u64 = trades.iter().map(|trade| trade.quantity).sum();
+        assert_eq!(total_quantity, 10);
+    }
 }
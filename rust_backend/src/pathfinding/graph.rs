@@ -0,0 +1,129 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+use crate::pathfinding::models::{Graph, MapNode};
+
+// Systems below this security status are weighted as costing several extra
+// "jumps" worth of risk, so Dijkstra prefers a longer high-sec route over a
+// shorter low-sec one when both exist.
+const LOW_SEC_THRESHOLD: f64 = 0.5;
+const LOW_SEC_PENALTY: f64 = 5.0;
+
+/// Builds the gate-adjacency graph from the SDE jumps table. Each
+/// `(from_system, to_system)` edge is treated as bidirectional, matching how
+/// stargates pair up in EVE.
+pub fn generate_graph(connections: &[(u64, u64)]) -> Graph {
+    let mut graph: Graph = HashMap::new();
+
+    for &(from, to) in connections {
+        graph.entry(from).or_insert_with(Vec::new).push(to);
+        graph.entry(to).or_insert_with(Vec::new).push(from);
+    }
+
+    graph
+}
+
+/// Security status per solar system, keyed the same way as `Graph` node ids.
+///
+/// Must come from solar-system rows (SDE `groupID` 5), not stargate rows —
+/// `Graph` nodes are solar-system ids (see `parse_gate_connections`, which
+/// resolves each stargate to the system it sits in), so keying this by
+/// stargate `item_id` would silently never match and every jump would fall
+/// back to the default weight.
+pub fn security_by_system(stations: &[MapNode]) -> HashMap<u64, f64> {
+    stations
+        .iter()
+        .filter(|node| node.group_id == 5)
+        .map(|node| (node.item_id, node.security))
+        .collect()
+}
+
+#[derive(PartialEq)]
+struct HeapEntry {
+    cost: f64,
+    system_id: u64,
+}
+
+impl Eq for HeapEntry {}
+
+impl Ord for HeapEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed so `BinaryHeap` (a max-heap) pops the lowest cost first.
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+impl PartialOrd for HeapEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Dijkstra over the gate graph, weighted so low-security systems cost more
+/// than a plain jump count. Returns the system-id path (including `start`
+/// and `end`) alongside its jump count, or `None` if no route exists.
+pub fn shortest_path(
+    graph: &Graph,
+    security: &HashMap<u64, f64>,
+    start: u64,
+    end: u64,
+) -> Option<(Vec<u64>, usize)> {
+    if start == end {
+        return Some((vec![start], 0));
+    }
+
+    let mut best_cost: HashMap<u64, f64> = HashMap::new();
+    let mut previous: HashMap<u64, u64> = HashMap::new();
+    let mut queue = BinaryHeap::new();
+
+    best_cost.insert(start, 0.0);
+    queue.push(HeapEntry { cost: 0.0, system_id: start });
+
+    while let Some(HeapEntry { cost, system_id }) = queue.pop() {
+        if system_id == end {
+            break;
+        }
+
+        if cost > *best_cost.get(&system_id).unwrap_or(&f64::INFINITY) {
+            continue;
+        }
+
+        let Some(neighbours) = graph.get(&system_id) else { continue };
+
+        for &neighbour in neighbours {
+            let edge_cost = jump_cost(security, neighbour);
+            let next_cost = cost + edge_cost;
+
+            if next_cost < *best_cost.get(&neighbour).unwrap_or(&f64::INFINITY) {
+                best_cost.insert(neighbour, next_cost);
+                previous.insert(neighbour, system_id);
+                queue.push(HeapEntry { cost: next_cost, system_id: neighbour });
+            }
+        }
+    }
+
+    if !best_cost.contains_key(&end) {
+        return None;
+    }
+
+    let mut path = vec![end];
+    let mut current = end;
+    while let Some(&prev) = previous.get(&current) {
+        path.push(prev);
+        current = prev;
+    }
+    path.reverse();
+
+    let jumps = path.len() - 1;
+    Some((path, jumps))
+}
+
+fn jump_cost(security: &HashMap<u64, f64>, destination_system: u64) -> f64 {
+    let destination_security = security.get(&destination_system).copied().unwrap_or(1.0);
+
+    if destination_security < LOW_SEC_THRESHOLD {
+        1.0 + LOW_SEC_PENALTY
+    } else {
+        1.0
+    }
+}
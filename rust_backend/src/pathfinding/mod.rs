@@ -1,15 +1,21 @@
 mod models;
 mod parser;
 mod graph;
-pub use models::{Location, Graph};
+pub use models::{Location, Graph, MapNode};
+pub use parser::parse_stations;
+pub use graph::{security_by_system, shortest_path};
 
-
-use crate::pathfinding::parser::parse_stations;
 use crate::pathfinding::graph::generate_graph;
 
-pub fn build() {
+pub struct PathfindingData {
+    pub stations: Vec<MapNode>,
+    pub graph: Graph,
+}
+
+pub fn build() -> PathfindingData {
     let stations = parse_stations().unwrap();
-    let gates_connnections = parse_gate_connections().unwrap()
-    let graph = generate_graph(&stations, &gates_connnections);
+    let gate_connections = parser::parse_gate_connections(&stations).unwrap();
+    let graph = generate_graph(&gate_connections);
 
+    PathfindingData { stations, graph }
 }
@@ -3,7 +3,7 @@ use std::str::FromStr;
 use std::collections::HashMap;
 
 
-#[derive(Deserialize, Serialize, Clone)]
+#[derive(Deserialize, Serialize, Clone, PartialEq)]
 pub struct Location {
     pub solar_system_id: i32,
     pub station_id: Option<i32>,
@@ -62,10 +62,15 @@ where
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize)]
 pub struct MapNode {
     pub item_id: u64,
     pub is_station: bool,
+    /// The SDE `groupID` this row was loaded from (15 = station, 10 =
+    /// stargate, 5 = solar system) — `is_station` alone can't tell a
+    /// stargate row from a solar-system row apart, and callers like
+    /// `security_by_system`/`parse_gate_connections` need to.
+    pub group_id: u64,
     pub solar_system_id: u64,
     pub region_id: u64,
     pub x: f64,
@@ -75,10 +80,14 @@ pub struct MapNode {
     pub security: f64,
 }
 
+/// One row of the SDE's stargate-jumps table: a stargate and the stargate
+/// it connects to. Both sides are stargate `item_id`s, not solar systems —
+/// `parse_gate_connections` resolves each to its `solar_system_id` via the
+/// already-parsed station/stargate list.
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct Connection {
-    stargate_id: u64,
-    destination_id: u64,
+    pub(crate) stargate_id: u64,
+    pub(crate) destination_id: u64,
 }
 
 
@@ -1,8 +1,9 @@
+use std::collections::HashMap;
 use std::error::Error;
 use std::fs::File;
 use csv;
 
-use crate::pathfinding::models::{RawNode, MapNode};
+use crate::pathfinding::models::{Connection, RawNode, MapNode};
 
 pub fn parse_stations() -> Result<Vec<MapNode>, Box<dyn Error>> {
     let file_path = "../mapDenormalize.csv";
@@ -20,11 +21,17 @@ pub fn parse_stations() -> Result<Vec<MapNode>, Box<dyn Error>> {
 
     println!("raw nodes: {:?}", raw_nodes.len());
 
+    // groupID 15 = station, 10 = stargate, 5 = solar system. Solar-system
+    // rows are kept alongside stations/stargates so `security_by_system`
+    // has real per-system security status to key Dijkstra's edge weights
+    // on, instead of accidentally reading stargate rows as if they were
+    // systems.
     let processed_nodes: Vec<MapNode> = raw_nodes.into_iter()
-        .filter(|node| [15, 10].contains(&node.groupID))
+        .filter(|node| [15, 10, 5].contains(&node.groupID))
         .map(|node| MapNode {
             item_id: node.itemID,
             is_station: node.groupID == 15,
+            group_id: node.groupID,
             solar_system_id: node.solarSystemID.unwrap_or_default(),
             region_id: node.regionID.unwrap_or_default(),
             x: node.x.unwrap_or_default(),
@@ -41,6 +48,41 @@ pub fn parse_stations() -> Result<Vec<MapNode>, Box<dyn Error>> {
     Ok(processed_nodes)
 }
 
-pub fn parse_gate_connections() -> Result<Vec<u64, u64>, Box<dyn Error>> {
-    unimplemented!();
+/// Resolves the SDE's stargate-jumps table into `(from_system, to_system)`
+/// edges `generate_graph` can build a `Graph` from. `stations` must already
+/// include stargates (`parse_stations` keeps groupID 10 alongside stations
+/// for exactly this reason) so each stargate's `item_id` can be mapped to
+/// the system it sits in.
+pub fn parse_gate_connections(stations: &[MapNode]) -> Result<Vec<(u64, u64)>, Box<dyn Error>> {
+    let file_path = "../stargates.csv";
+    let file = File::open(file_path)?;
+
+    let mut rdr = csv::Reader::from_reader(file);
+
+    let stargate_system: HashMap<u64, u64> = stations.iter()
+        .filter(|node| node.group_id == 10)
+        .map(|node| (node.item_id, node.solar_system_id))
+        .collect();
+
+    let mut connections: Vec<(u64, u64)> = Vec::new();
+    for result in rdr.deserialize() {
+        let connection: Connection = match result {
+            Ok(connection) => connection,
+            Err(e) => {
+                println!("Error deserializing record: {}", e);
+                continue;
+            }
+        };
+
+        let from_system = stargate_system.get(&connection.stargate_id);
+        let to_system = stargate_system.get(&connection.destination_id);
+
+        if let (Some(&from_system), Some(&to_system)) = (from_system, to_system) {
+            connections.push((from_system, to_system));
+        }
+    }
+
+    println!("gate connections: {:?}", connections.len());
+
+    Ok(connections)
 }
\ No newline at end of file
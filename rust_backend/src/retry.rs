@@ -0,0 +1,181 @@
+use std::error::Error;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use rand::Rng;
+use reqwest::{Response, StatusCode};
+use tokio::time::sleep;
+
+// ESI tells us how many errors we have left in the current window, and how
+// long until that window resets, via these two response headers.
+const ERROR_LIMIT_REMAIN_HEADER: &str = "x-esi-error-limit-remain";
+const ERROR_LIMIT_RESET_HEADER: &str = "x-esi-error-limit-reset";
+
+// Stop sending new requests once the remaining error budget drops below this,
+// and wait out the reset window instead of risking a temporary ban.
+const ERROR_LIMIT_THRESHOLD: u64 = 5;
+
+/// Shared rate-limit state parsed from ESI's error-limit headers.
+///
+/// `remain`/`reset_at_unix` are updated after every response so every caller
+/// using this policy backs off together instead of each tripping the limit
+/// independently.
+struct ErrorLimitState {
+    remain: AtomicU64,
+    reset_at_unix: AtomicU64,
+}
+
+impl ErrorLimitState {
+    fn new() -> Self {
+        ErrorLimitState {
+            remain: AtomicU64::new(100),
+            reset_at_unix: AtomicU64::new(0),
+        }
+    }
+
+    fn record(&self, response: &Response) {
+        if let Some(remain) = header_as_u64(response, ERROR_LIMIT_REMAIN_HEADER) {
+            self.remain.store(remain, Ordering::Relaxed);
+        }
+
+        if let Some(reset) = header_as_u64(response, ERROR_LIMIT_RESET_HEADER) {
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            self.reset_at_unix.store(now + reset, Ordering::Relaxed);
+        }
+    }
+
+    /// Seconds to wait before the next request is safe to send, or `None` if
+    /// we still have budget left.
+    fn cooldown(&self) -> Option<Duration> {
+        if self.remain.load(Ordering::Relaxed) >= ERROR_LIMIT_THRESHOLD {
+            return None;
+        }
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let reset_at = self.reset_at_unix.load(Ordering::Relaxed);
+
+        if reset_at > now {
+            Some(Duration::from_secs(reset_at - now))
+        } else {
+            None
+        }
+    }
+}
+
+fn header_as_u64(response: &Response, name: &str) -> Option<u64> {
+    response
+        .headers()
+        .get(name)?
+        .to_str()
+        .ok()?
+        .parse::<u64>()
+        .ok()
+}
+
+/// A single `reqwest::Client` shared by every ESI call, wrapped with
+/// exponential-backoff retries and error-limit-aware throttling.
+///
+/// Modeled on ethers-rs's `RetryClient`/`HttpRateLimitRetryPolicy`: retry on
+/// `503`/`420`/`429` and connect/timeout errors with `base * 2^attempt` plus
+/// jitter, and proactively sleep out ESI's rolling error-limit window before
+/// it actually bans us.
+pub struct RetryPolicy {
+    client: reqwest::Client,
+    max_retries: u32,
+    base_backoff: Duration,
+    error_limit: ErrorLimitState,
+}
+
+impl RetryPolicy {
+    pub fn new(max_retries: u32, base_backoff: Duration) -> Result<Self, Box<dyn Error>> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()?;
+
+        Ok(RetryPolicy {
+            client,
+            max_retries,
+            base_backoff,
+            error_limit: ErrorLimitState::new(),
+        })
+    }
+
+    /// Issue a GET request, retrying transient failures and honoring ESI's
+    /// error-limit budget. `configure` can attach headers/auth before send.
+    pub async fn get(
+        &self,
+        url: &str,
+        configure: impl Fn(reqwest::RequestBuilder) -> reqwest::RequestBuilder,
+    ) -> Result<Response, Box<dyn Error>> {
+        self.send_with_retry(|| self.client.get(url), configure).await
+    }
+
+    /// Issue a POST request through the same retry/error-budget policy as
+    /// `get` — every ESI call should share this one policy so the error-limit
+    /// budget it tracks reflects the whole crate's traffic, not just GETs.
+    pub async fn post(
+        &self,
+        url: &str,
+        configure: impl Fn(reqwest::RequestBuilder) -> reqwest::RequestBuilder,
+    ) -> Result<Response, Box<dyn Error>> {
+        self.send_with_retry(|| self.client.post(url), configure).await
+    }
+
+    async fn send_with_retry(
+        &self,
+        new_request: impl Fn() -> reqwest::RequestBuilder,
+        configure: impl Fn(reqwest::RequestBuilder) -> reqwest::RequestBuilder,
+    ) -> Result<Response, Box<dyn Error>> {
+        for attempt in 0..=self.max_retries {
+            self.wait_for_error_budget().await;
+
+            let request = configure(new_request());
+            let result = request.send().await;
+
+            match result {
+                Ok(response) => {
+                    self.error_limit.record(&response);
+
+                    if !Self::is_retryable_status(response.status()) {
+                        return Ok(response);
+                    }
+
+                    if attempt == self.max_retries {
+                        return Ok(response);
+                    }
+                }
+                Err(e) => {
+                    if !(e.is_connect() || e.is_timeout()) || attempt == self.max_retries {
+                        return Err(Box::new(e));
+                    }
+                }
+            }
+
+            sleep(self.backoff_with_jitter(attempt)).await;
+        }
+
+        unreachable!("loop always returns on the final attempt")
+    }
+
+    fn is_retryable_status(status: StatusCode) -> bool {
+        matches!(status.as_u16(), 503 | 420 | 429)
+    }
+
+    fn backoff_with_jitter(&self, attempt: u32) -> Duration {
+        let exp = self.base_backoff * 2u32.saturating_pow(attempt);
+        let jitter_ms = rand::thread_rng().gen_range(0..100);
+        exp + Duration::from_millis(jitter_ms)
+    }
+
+    async fn wait_for_error_budget(&self) {
+        if let Some(cooldown) = self.error_limit.cooldown() {
+            sleep(cooldown).await;
+        }
+    }
+}
@@ -1,4 +1,11 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+use crate::items::ItemType;
+use crate::market::{allocate_matches, Isk, Order, Trade, TypeHistory};
+use crate::pathfinding::{security_by_system, shortest_path, Graph, Location, MapNode};
 
 struct Item {
     profit: f32,
@@ -68,41 +75,324 @@ fn knapsack(items: &[Item], max_cost: f32, max_cargo: f32) -> (f32, Vec<(usize,
     best_state.values().max_by(|&(p1, _), &(p2, _)| p1.partial_cmp(&p2).unwrap()).unwrap_or(&(0.0, vec![])).clone()
 }
 
-fn main() {
-    // Example usage with some items defined
-    let items = vec![
-        Item {
-            profit: 60.0,
-            cost: 0.1,
-            cargo: 0.2,
-            quantity: 100,
-        },
-        Item {
-            profit: 100.0,
-            cost: 0.2,
-            cargo: 0.5,
-            quantity: 50,
-        },
-        Item {
-            profit: 100.0,
-            cost: 0.1,
-            cargo: 0.5,
-            quantity: 50,
-        },
-        Item {
-            profit: 100.0,
-            cost: 0.2,
-            cargo: 0.3,
-            quantity: 50,
-        },
-    ];
-
-    let max_cost = 50.0;
-    let max_cargo = 50.0;
-
-    let (max_profit, item_selections) = knapsack(&items, max_cost, max_cargo);
-    println!("Maximum profit: {}", max_profit);
-    for (index, quantity) in item_selections {
-        println!("Item {} taken with quantity: {}", index, quantity);
+/// Greedy-fractional two-constraint knapsack: fill by descending
+/// profit-per-m³ density until cargo or budget runs out, letting the last
+/// item to run out take a fractional quantity instead of rounding down.
+/// Cheaper than `knapsack`'s discretized DP. Optimal for divisible items
+/// when cargo is the binding limit; when the budget runs out first, the
+/// basket is not guaranteed to be the most profitable one. Reach for the DP
+/// instead when a caller needs whole-unit exactness.
+fn greedy_fill(items: &[Item], max_cost: f32, max_cargo: f32) -> (f32, Vec<(usize, f32)>) {
+    let mut order: Vec<usize> = (0..items.len()).collect();
+    order.sort_by(|&a, &b| {
+        let density_a = items[a].profit / items[a].cargo;
+        let density_b = items[b].profit / items[b].cargo;
+        density_b.partial_cmp(&density_a).unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    let mut remaining_cost = max_cost;
+    let mut remaining_cargo = max_cargo;
+    let mut total_profit = 0.0;
+    let mut fills = Vec::new();
+
+    for index in order {
+        let item = &items[index];
+        if item.cargo <= 0.0 || item.cost < 0.0 {
+            continue;
+        }
+
+        let max_by_cargo = remaining_cargo / item.cargo;
+        let max_by_cost = if item.cost > 0.0 { remaining_cost / item.cost } else { f32::INFINITY };
+        let quantity = max_by_cargo.min(max_by_cost).min(item.quantity as f32).max(0.0);
+
+        if quantity <= 0.0 {
+            continue;
+        }
+
+        total_profit += item.profit * quantity;
+        remaining_cargo -= item.cargo * quantity;
+        remaining_cost -= item.cost * quantity;
+        fills.push((index, quantity));
+
+        if remaining_cargo <= 0.0 || remaining_cost <= 0.0 {
+            break;
+        }
     }
+
+    (total_profit, fills)
+}
+
+// Fallback when a `type_id` isn't in the loaded SDE item-type table (e.g.
+// the table failed to load, or ESI returned a type the SDE snapshot
+// predates): still treat it as occupying a unit of hold space rather than
+// silently excluding it from the basket.
+const PLACEHOLDER_CARGO_PER_UNIT: f32 = 1.0;
+
+fn cargo_per_unit(type_id: u64, item_types: &HashMap<u64, ItemType>) -> f32 {
+    item_types.get(&type_id).map_or(PLACEHOLDER_CARGO_PER_UNIT, |item_type| item_type.volume as f32)
+}
+
+#[derive(Serialize)]
+pub struct RouteWaypoint {
+    pub system_id: u64,
+}
+
+#[derive(Serialize)]
+pub struct ManifestEntry {
+    pub type_id: u64,
+    pub quantity: usize,
+    pub buy_at: u64,
+    pub sell_at: u64,
+}
+
+#[derive(Serialize)]
+pub struct RoutePlan {
+    pub waypoints: Vec<RouteWaypoint>,
+    pub jumps: usize,
+    pub total_profit: f32,
+    pub profit_per_jump: f32,
+    pub manifest: Vec<ManifestEntry>,
+}
+
+/// Ties the gate graph to the knapsack solver: find the destination system
+/// reachable from `location` whose buy/sell spread, filled into the ship's
+/// hold under `max_cargo`/`max_budget`, yields the best profit-per-jump.
+pub fn plan_route(
+    location: &Location,
+    graph: &Graph,
+    stations: &[MapNode],
+    orders: &[Order],
+    item_types: &HashMap<u64, ItemType>,
+    max_cargo: f32,
+    max_budget: f32,
+) -> Option<RoutePlan> {
+    let origin_system = location.solar_system_id as u64;
+    let security = security_by_system(stations);
+
+    let station_by_id: HashMap<u64, &MapNode> = stations
+        .iter()
+        .filter(|node| node.is_station)
+        .map(|node| (node.item_id, node))
+        .collect();
+
+    let sell_orders: Vec<&Order> = orders.iter().filter(|order| !order.is_buy_order()).collect();
+    let buy_orders: Vec<&Order> = orders.iter().filter(|order| order.is_buy_order()).collect();
+
+    let mut sells_by_type: HashMap<u64, Vec<&Order>> = HashMap::new();
+    for &sell_order in &sell_orders {
+        sells_by_type.entry(sell_order.type_id()).or_default().push(sell_order);
+    }
+
+    let destination_systems: HashSet<u64> = station_by_id
+        .values()
+        .map(|station| station.solar_system_id)
+        .filter(|&system_id| system_id != origin_system)
+        .collect();
+
+    let mut best: Option<RoutePlan> = None;
+
+    for &destination_system in &destination_systems {
+        let Some((path, jumps)) = shortest_path(graph, &security, origin_system, destination_system) else {
+            continue;
+        };
+        if jumps == 0 {
+            continue;
+        }
+
+        let destination_stations: Vec<u64> = station_by_id
+            .iter()
+            .filter(|(_, station)| station.solar_system_id == destination_system)
+            .map(|(&id, _)| id)
+            .collect();
+
+        let mut buys_by_type: HashMap<u64, Vec<&Order>> = HashMap::new();
+        for &buy_order in &buy_orders {
+            if destination_stations.contains(&buy_order.location_id()) {
+                buys_by_type.entry(buy_order.type_id()).or_default().push(buy_order);
+            }
+        }
+
+        // Matched, not cross-producted: `allocate_matches` consumes each
+        // order's `volume_remain` as quantity is allocated to a candidate, so
+        // the same unit of stock can't back two different candidates that the
+        // knapsack would otherwise treat as independent items.
+        let mut candidates: Vec<(&Order, &Order, Isk, u64)> = Vec::new();
+        for (type_id, sells) in &sells_by_type {
+            let Some(buys) = buys_by_type.get(type_id) else { continue };
+
+            let matches = allocate_matches(sells, buys, |sell_order, buy_order, quantity| {
+                let profit_per_unit = buy_order.price() - sell_order.price();
+                profit_per_unit.is_positive().then_some((sell_order, buy_order, profit_per_unit, quantity))
+            });
+            candidates.extend(matches);
+        }
+
+        if candidates.is_empty() {
+            continue;
+        }
+
+        let items: Vec<Item> = candidates
+            .iter()
+            .map(|(sell_order, _buy_order, profit_per_unit, quantity)| Item {
+                profit: profit_per_unit.to_f64() as f32,
+                cost: sell_order.price().to_f64() as f32,
+                cargo: cargo_per_unit(sell_order.type_id(), item_types),
+                quantity: *quantity as usize,
+            })
+            .collect();
+
+        let (total_profit, selections) = knapsack(&items, max_budget, max_cargo);
+        if total_profit <= 0.0 {
+            continue;
+        }
+
+        let manifest: Vec<ManifestEntry> = selections
+            .iter()
+            .map(|&(index, quantity)| {
+                let (sell_order, buy_order, _, _) = candidates[index];
+                ManifestEntry {
+                    type_id: sell_order.type_id(),
+                    quantity,
+                    buy_at: sell_order.location_id(),
+                    sell_at: buy_order.location_id(),
+                }
+            })
+            .collect();
+
+        let profit_per_jump = total_profit / jumps as f32;
+
+        let is_better = best.as_ref().map_or(true, |current| profit_per_jump > current.profit_per_jump);
+        if is_better {
+            best = Some(RoutePlan {
+                waypoints: path.into_iter().map(|system_id| RouteWaypoint { system_id }).collect(),
+                jumps,
+                total_profit,
+                profit_per_jump,
+                manifest,
+            });
+        }
+    }
+
+    best
+}
+
+#[derive(Serialize)]
+pub struct HaulManifestEntry {
+    pub type_id: u64,
+    /// Fractional fill from the greedy solver — a caller that needs whole
+    /// units should round down before placing buy orders.
+    pub quantity: f32,
+    pub buy_at: u64,
+    pub sell_at: u64,
+}
+
+#[derive(Serialize)]
+pub struct HaulPlan {
+    pub waypoints: Vec<RouteWaypoint>,
+    pub jumps: usize,
+    pub total_profit: f32,
+    pub isk_per_jump: f32,
+    pub manifest: Vec<HaulManifestEntry>,
+}
+
+/// Answers "given my ship's hold and wallet, and where I am, what should I
+/// buy here and sell there": groups `Trade::create_trades`'s liquidity- and
+/// tax-adjusted trades bought in the origin system by destination system,
+/// then greedily fills the hold
+/// by profit-per-m³ under both the cargo and budget constraints.
+pub fn plan_haul(
+    location: &Location,
+    graph: &Graph,
+    stations: &[MapNode],
+    orders: &[Order],
+    history: &HashMap<u64, TypeHistory>,
+    item_types: &HashMap<u64, ItemType>,
+    broker_fee_rate: Decimal,
+    cargo_m3: f32,
+    budget_isk: f32,
+) -> Option<HaulPlan> {
+    let origin_system = location.solar_system_id as u64;
+    let security = security_by_system(stations);
+
+    let station_by_id: HashMap<u64, &MapNode> = stations
+        .iter()
+        .filter(|node| node.is_station)
+        .map(|node| (node.item_id, node))
+        .collect();
+
+    let sell_orders: Vec<Order> = orders.iter().filter(|order| !order.is_buy_order()).cloned().collect();
+    let buy_orders: Vec<Order> = orders.iter().filter(|order| order.is_buy_order()).cloned().collect();
+    let trades = Trade::create_trades(&sell_orders, &buy_orders, broker_fee_rate, history, item_types);
+
+    let mut trades_by_destination: HashMap<u64, Vec<&Trade>> = HashMap::new();
+    for trade in &trades {
+        let bought_here = station_by_id
+            .get(&trade.buy_at)
+            .map_or(false, |station| station.solar_system_id == origin_system);
+        if !bought_here {
+            continue;
+        }
+
+        if let Some(station) = station_by_id.get(&trade.sell_at) {
+            trades_by_destination.entry(station.solar_system_id).or_default().push(trade);
+        }
+    }
+
+    let mut best: Option<HaulPlan> = None;
+
+    for (&destination_system, candidates) in &trades_by_destination {
+        if destination_system == origin_system {
+            continue;
+        }
+
+        let Some((path, jumps)) = shortest_path(graph, &security, origin_system, destination_system) else {
+            continue;
+        };
+        if jumps == 0 {
+            continue;
+        }
+
+        let items: Vec<Item> = candidates
+            .iter()
+            .map(|trade| Item {
+                profit: trade.gross_profit.to_f64() as f32 / trade.quantity as f32,
+                cost: trade.buy_price.to_f64() as f32,
+                cargo: cargo_per_unit(trade.type_id, item_types),
+                quantity: trade.quantity as usize,
+            })
+            .collect();
+
+        let (total_profit, fills) = greedy_fill(&items, budget_isk, cargo_m3);
+        if total_profit <= 0.0 {
+            continue;
+        }
+
+        let manifest: Vec<HaulManifestEntry> = fills
+            .iter()
+            .map(|&(index, quantity)| {
+                let trade = candidates[index];
+                HaulManifestEntry {
+                    type_id: trade.type_id,
+                    quantity,
+                    buy_at: trade.buy_at,
+                    sell_at: trade.sell_at,
+                }
+            })
+            .collect();
+
+        let isk_per_jump = total_profit / jumps as f32;
+
+        let is_better = best.as_ref().map_or(true, |current| isk_per_jump > current.isk_per_jump);
+        if is_better {
+            best = Some(HaulPlan {
+                waypoints: path.into_iter().map(|system_id| RouteWaypoint { system_id }).collect(),
+                jumps,
+                total_profit,
+                isk_per_jump,
+                manifest,
+            });
+        }
+    }
+
+    best
 }
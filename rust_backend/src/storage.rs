@@ -0,0 +1,257 @@
+use std::collections::HashMap;
+use std::env;
+use std::error::Error;
+
+use chrono::{DateTime, Utc};
+use dotenv::dotenv;
+use rust_decimal::Decimal;
+use tokio_postgres::{Client, NoTls, Row};
+
+use crate::items::ItemType;
+use crate::market::{Isk, Order, Orders, Trade};
+
+/// Persists `Order`/`Trade` snapshots to Postgres so the order book survives
+/// a restart and past trade opportunities can be analyzed later, instead of
+/// living only in the in-memory `Orders`/`watch` channels.
+///
+/// Raw orders and derived trades are written through separate methods (and
+/// can be backfilled independently via `backfill_trades`) because a trade
+/// is *derived* from a point-in-time order book plus market history, and
+/// that derivation logic can change independently of how orders are
+/// ingested.
+pub struct Storage {
+    client: Client,
+}
+
+impl Storage {
+    pub async fn new() -> Result<Self, Box<dyn Error>> {
+        dotenv().ok();
+
+        let database_url = env::var("DATABASE_URL").map_err(|e| format!("DATABASE_URL error: {}", e))?;
+
+        let (client, connection) = tokio_postgres::connect(&database_url, NoTls).await?;
+
+        // The connection object does the actual I/O; it has to be driven on
+        // its own task or nothing sent through `client` is ever sent.
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                eprintln!("Postgres connection error: {}", e);
+            }
+        });
+
+        let storage = Storage { client };
+        storage.ensure_schema().await?;
+
+        Ok(storage)
+    }
+
+    async fn ensure_schema(&self) -> Result<(), Box<dyn Error>> {
+        self.client.batch_execute(
+            "
+            CREATE TABLE IF NOT EXISTS order_snapshots (
+                region_id       BIGINT NOT NULL,
+                order_id        BIGINT NOT NULL,
+                observed_at     TIMESTAMPTZ NOT NULL,
+                expires_at      TIMESTAMPTZ NOT NULL,
+                type_id         BIGINT NOT NULL,
+                location_id     BIGINT NOT NULL,
+                system_id       BIGINT NOT NULL,
+                is_buy_order    BOOLEAN NOT NULL,
+                price           TEXT NOT NULL,
+                volume_remain   BIGINT NOT NULL,
+                volume_total    BIGINT NOT NULL,
+                min_volume      BIGINT NOT NULL,
+                duration        BIGINT NOT NULL,
+                issued          TIMESTAMPTZ NOT NULL,
+                order_range     TEXT NOT NULL,
+                PRIMARY KEY (region_id, order_id, observed_at)
+            );
+
+            CREATE TABLE IF NOT EXISTS trade_snapshots (
+                id              BIGSERIAL PRIMARY KEY,
+                observed_at     TIMESTAMPTZ NOT NULL,
+                type_id         BIGINT NOT NULL,
+                quantity        BIGINT NOT NULL,
+                buy_at          BIGINT NOT NULL,
+                sell_at         BIGINT NOT NULL,
+                buy_price       TEXT NOT NULL,
+                net_profit      TEXT NOT NULL,
+                days_to_sell    DOUBLE PRECISION,
+                total_volume_m3 DOUBLE PRECISION
+            );
+            ",
+        ).await?;
+
+        Ok(())
+    }
+
+    /// Writes one row per order in `orders`, keyed by `(region_id,
+    /// order_id, observed_at)` so the same order fetched at two different
+    /// times produces two rows instead of overwriting history.
+    pub async fn insert_order_snapshot(
+        &self,
+        region: u64,
+        orders: &[Order],
+        observed_at: DateTime<Utc>,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), Box<dyn Error>> {
+        for order in orders {
+            self.client.execute(
+                "INSERT INTO order_snapshots (
+                    region_id, order_id, observed_at, expires_at, type_id, location_id,
+                    system_id, is_buy_order, price, volume_remain, volume_total,
+                    min_volume, duration, issued, order_range
+                ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
+                ON CONFLICT (region_id, order_id, observed_at) DO NOTHING",
+                &[
+                    &(region as i64),
+                    &(order.order_id as i64),
+                    &observed_at,
+                    &expires_at,
+                    &(order.type_id as i64),
+                    &(order.location_id as i64),
+                    &(order.system_id as i64),
+                    &order.is_buy_order,
+                    &order.price.to_string(),
+                    &(order.volume_remain as i64),
+                    &(order.volume_total as i64),
+                    &(order.min_volume as i64),
+                    &(order.duration as i64),
+                    &order.issued,
+                    &order.range,
+                ],
+            ).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes one row per trade `create_trades` surfaced, tagged with the
+    /// time the underlying order book was observed.
+    pub async fn insert_trade_snapshot(&self, trades: &[Trade], observed_at: DateTime<Utc>) -> Result<(), Box<dyn Error>> {
+        for trade in trades {
+            self.client.execute(
+                "INSERT INTO trade_snapshots (
+                    observed_at, type_id, quantity, buy_at, sell_at, buy_price,
+                    net_profit, days_to_sell, total_volume_m3
+                ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+                &[
+                    &observed_at,
+                    &(trade.type_id as i64),
+                    &(trade.quantity as i64),
+                    &(trade.buy_at as i64),
+                    &(trade.sell_at as i64),
+                    &trade.buy_price.to_string(),
+                    &trade.gross_profit.to_string(),
+                    &trade.days_to_sell,
+                    &trade.total_volume_m3,
+                ],
+            ).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Loads the most recent non-expired snapshot for each region into an
+    /// `Orders`, so the poll loop resumes from where it left off instead of
+    /// starting empty after a restart.
+    pub async fn latest_orders(&self) -> Result<Orders, Box<dyn Error>> {
+        let mut orders = Orders::new();
+
+        let regions = self.client.query(
+            "SELECT DISTINCT ON (region_id) region_id, observed_at, expires_at
+             FROM order_snapshots
+             WHERE expires_at > now()
+             ORDER BY region_id, observed_at DESC",
+            &[],
+        ).await?;
+
+        for region_row in regions {
+            let region: i64 = region_row.get("region_id");
+            let observed_at: DateTime<Utc> = region_row.get("observed_at");
+            let expires_at: DateTime<Utc> = region_row.get("expires_at");
+
+            let rows = self.client.query(
+                "SELECT order_id, type_id, location_id, system_id, is_buy_order, price,
+                        volume_remain, volume_total, min_volume, duration, issued, order_range
+                 FROM order_snapshots
+                 WHERE region_id = $1 AND observed_at = $2",
+                &[&region, &observed_at],
+            ).await?;
+
+            let region_orders: Result<Vec<Order>, Box<dyn Error>> = rows.iter().map(row_to_order).collect();
+
+            orders.insert(region as u64, region_orders?, expires_at, None);
+        }
+
+        Ok(orders)
+    }
+
+    /// Replays every stored order snapshot through `Trade::create_trades`
+    /// and persists the result, reconstructing historical trade
+    /// opportunities without needing the original market history (which
+    /// isn't snapshotted, so liquidity pruning is skipped and every trade
+    /// comes back with `days_to_sell: None`).
+    pub async fn backfill_trades(&self, broker_fee_rate: Decimal, item_types: &HashMap<u64, ItemType>) -> Result<(), Box<dyn Error>> {
+        let snapshots = self.client.query(
+            "SELECT DISTINCT region_id, observed_at FROM order_snapshots ORDER BY observed_at",
+            &[],
+        ).await?;
+
+        let no_history = HashMap::new();
+
+        for snapshot in snapshots {
+            let region: i64 = snapshot.get("region_id");
+            let observed_at: DateTime<Utc> = snapshot.get("observed_at");
+
+            let rows = self.client.query(
+                "SELECT order_id, type_id, location_id, system_id, is_buy_order, price,
+                        volume_remain, volume_total, min_volume, duration, issued, order_range
+                 FROM order_snapshots
+                 WHERE region_id = $1 AND observed_at = $2",
+                &[&region, &observed_at],
+            ).await?;
+
+            let orders: Vec<Order> = rows.iter().map(row_to_order).collect::<Result<_, _>>()?;
+            let sell_orders: Vec<Order> = orders.iter().filter(|order| !order.is_buy_order).cloned().collect();
+            let buy_orders: Vec<Order> = orders.iter().filter(|order| order.is_buy_order).cloned().collect();
+
+            let trades = Trade::create_trades(&sell_orders, &buy_orders, broker_fee_rate, &no_history, item_types);
+            self.insert_trade_snapshot(&trades, observed_at).await?;
+
+            println!("backfilled {} trades for region {} @ {}", trades.len(), region, observed_at);
+        }
+
+        Ok(())
+    }
+}
+
+fn row_to_order(row: &Row) -> Result<Order, Box<dyn Error>> {
+    let order_id: i64 = row.get("order_id");
+    let type_id: i64 = row.get("type_id");
+    let location_id: i64 = row.get("location_id");
+    let system_id: i64 = row.get("system_id");
+    let is_buy_order: bool = row.get("is_buy_order");
+    let price: String = row.get("price");
+    let volume_remain: i64 = row.get("volume_remain");
+    let volume_total: i64 = row.get("volume_total");
+    let min_volume: i64 = row.get("min_volume");
+    let duration: i64 = row.get("duration");
+    let issued: DateTime<Utc> = row.get("issued");
+    let range: String = row.get("order_range");
+
+    Ok(Order {
+        duration: duration as u64,
+        is_buy_order,
+        issued,
+        location_id: location_id as u64,
+        min_volume: min_volume as u64,
+        order_id: order_id as u64,
+        price: price.parse::<Isk>()?,
+        range,
+        system_id: system_id as u64,
+        type_id: type_id as u64,
+        volume_remain: volume_remain as u64,
+        volume_total: volume_total as u64,
+    })
+}